@@ -0,0 +1,221 @@
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use ipc_channel::ipc::IpcError;
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::{RecvError, TryRecvError};
+use super::{AsyncIpcReceiver, ChannelError};
+
+struct Shared<T> {
+    receiver: AsyncIpcReceiver<T>,
+    // emptied once the channel disconnects, so subscribers see it close
+    slot: Mutex<Option<broadcast::Sender<T>>>,
+    // only one subscriber at a time pulls the next message off the channel, so they go out in order
+    fetching: tokio::sync::Mutex<()>
+}
+
+impl<T> Shared<T>
+    where T: 'static + Send + Clone + for<'de> Deserialize<'de> + Serialize
+{
+    fn lock(&self) -> MutexGuard<'_, Option<broadcast::Sender<T>>> {
+        self.slot.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn subscribe(self: &Arc<Self>) -> IpcSubscriber<T> {
+        let receiver = match &*self.lock() {
+            Some(sender) => sender.subscribe(),
+            None => broadcast::channel(1).1
+        };
+
+        IpcSubscriber { receiver, shared: Arc::clone(self) }
+    }
+
+    async fn fetch(&self) {
+        loop {
+            match self.receiver.recv().await {
+                Ok(t) => {
+                    if let Some(sender) = &*self.lock() {
+                        let _ = sender.send(t);
+                    }
+                    return
+                }
+                // a single message that failed to deserialize doesn't end the stream
+                Err(ChannelError::Ipc(IpcError::Bincode(_))) => continue,
+                Err(_) => {
+                    self.lock().take();
+                    return
+                }
+            }
+        }
+    }
+}
+
+/// Fans the messages of an [`AsyncIpcReceiver`] out to any number of subscribers.
+///
+/// There is no thread forwarding messages in the background: whichever subscriber runs out of messages first
+/// takes the next one off the channel and hands it to everyone, so messages stay in the channel
+/// while nobody is receiving. Messages are only delivered to subscribers that exist when they are taken,
+/// and subscribers that fall more than `capacity` messages behind see
+/// [`broadcast::error::RecvError::Lagged`] just like with a plain tokio broadcast channel.
+///
+/// Once the [`IpcBroadcast`] and all its subscribers are dropped the channel is closed.
+pub struct IpcBroadcast<T> {
+    shared: Arc<Shared<T>>
+}
+
+impl<T> IpcBroadcast<T>
+    where T: 'static + Send + Clone + for<'de> Deserialize<'de> + Serialize
+{
+    /// Subscribes to every message taken off the channel from now on,
+    /// the subscriber is already closed if the channel has disconnected.
+    pub fn subscribe(&self) -> IpcSubscriber<T> {
+        self.shared.subscribe()
+    }
+
+    pub fn receiver_count(&self) -> usize {
+        self.shared.lock().as_ref().map_or(0, broadcast::Sender::receiver_count)
+    }
+}
+
+/// A subscriber of an [`IpcBroadcast`].
+pub struct IpcSubscriber<T> {
+    receiver: broadcast::Receiver<T>,
+    shared: Arc<Shared<T>>
+}
+
+impl<T> IpcSubscriber<T>
+    where T: 'static + Send + Clone + for<'de> Deserialize<'de> + Serialize
+{
+    /// Receives the next message, taking it off the channel if no other subscriber already did.
+    ///
+    /// Cancelling it never loses a message for the other subscribers.
+    pub async fn recv(&mut self) -> Result<T, RecvError> {
+        loop {
+            if let Some(received) = self.try_take() {
+                return received
+            }
+
+            let shared = Arc::clone(&self.shared);
+            let _fetching = shared.fetching.lock().await;
+            // another subscriber may have taken one off the channel while we waited for our turn
+            if let Some(received) = self.try_take() {
+                return received
+            }
+            shared.fetch().await;
+        }
+    }
+
+    fn try_take(&mut self) -> Option<Result<T, RecvError>> {
+        match self.receiver.try_recv() {
+            Ok(t) => Some(Ok(t)),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Lagged(skipped)) => Some(Err(RecvError::Lagged(skipped))),
+            Err(TryRecvError::Closed) => Some(Err(RecvError::Closed))
+        }
+    }
+
+    /// Subscribes to every message taken off the channel from now on, just like [`IpcBroadcast::subscribe`].
+    pub fn resubscribe(&self) -> Self {
+        self.shared.subscribe()
+    }
+}
+
+impl<T> AsyncIpcReceiver<T>
+    where T: 'static + Send + Clone + for<'de> Deserialize<'de> + Serialize
+{
+    /// Shares every incoming message with all subscribers of the returned [`IpcBroadcast`],
+    /// handing back the first subscriber so nothing that arrives right away is missed.
+    pub fn broadcast(self, capacity: usize) -> (IpcBroadcast<T>, IpcSubscriber<T>) {
+        let (sender, _) = broadcast::channel(capacity);
+        let shared = Arc::new(Shared {
+            receiver: self,
+            slot: Mutex::new(Some(sender)),
+            fetching: tokio::sync::Mutex::new(())
+        });
+
+        let first = shared.subscribe();
+        (IpcBroadcast { shared }, first)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+    use ipc_channel::ipc;
+    use tokio::sync::broadcast::error::RecvError;
+    use super::AsyncIpcReceiver;
+
+    #[tokio::test]
+    async fn every_subscriber_sees_every_message() {
+        let (tx, rx) = ipc::channel::<u32>().unwrap();
+        let (handle, mut first) = AsyncIpcReceiver::new(rx).broadcast(8);
+        let mut second = handle.subscribe();
+        assert_eq!(handle.receiver_count(), 2);
+
+        for i in 0..3 {
+            tx.send(i).unwrap();
+        }
+
+        for subscriber in [&mut first, &mut second] {
+            for i in 0..3 {
+                assert_eq!(subscriber.recv().await.unwrap(), i);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn subscribers_close_with_the_channel() {
+        let (tx, rx) = ipc::channel::<u32>().unwrap();
+        let (handle, mut first) = AsyncIpcReceiver::new(rx).broadcast(8);
+
+        tx.send(1).unwrap();
+        drop(tx);
+
+        assert_eq!(first.recv().await.unwrap(), 1);
+        assert!(matches!(first.recv().await, Err(RecvError::Closed)));
+        assert!(matches!(handle.subscribe().recv().await, Err(RecvError::Closed)));
+        assert_eq!(handle.receiver_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn concurrent_subscribers_see_messages_in_order() {
+        let (tx, rx) = ipc::channel::<u32>().unwrap();
+        let (handle, first) = AsyncIpcReceiver::new(rx).broadcast(8);
+
+        let subscribers: Vec<_> = [first, handle.subscribe(), handle.subscribe()]
+            .into_iter()
+            .map(|mut subscriber| tokio::spawn(async move {
+                let mut received = vec![];
+                for _ in 0..5 {
+                    received.push(subscriber.recv().await.unwrap());
+                }
+                received
+            }))
+            .collect();
+
+        for i in 0..5 {
+            tx.send(i).unwrap();
+        }
+
+        for subscriber in subscribers {
+            assert_eq!(subscriber.await.unwrap(), [0, 1, 2, 3, 4]);
+        }
+    }
+
+    #[tokio::test]
+    async fn dropping_every_subscriber_hangs_up() {
+        let (tx, rx) = ipc::channel::<u32>().unwrap();
+        let (handle, mut first) = AsyncIpcReceiver::new(rx).broadcast(8);
+
+        // leave a receive pending on the channel, the pool gives it up once it notices
+        let pending = tokio::time::timeout(Duration::from_millis(1), first.recv()).await;
+        assert!(pending.is_err());
+        drop(handle);
+        drop(first);
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while tx.send(0).is_ok() {
+            assert!(Instant::now() < deadline, "the channel outlived its subscribers");
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    }
+}
@@ -0,0 +1,112 @@
+use std::future::Future;
+use std::io;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::thread;
+use std::time::Duration;
+use ipc_channel::ipc::{IpcBytesReceiver, IpcBytesSender, TryRecvError};
+use super::{impl_future, ChannelError, ChannelStats, IpcReceiveFuture, RawReceiver, RawSender, ReceiverCore, SendRequest, Worker};
+
+impl RawReceiver for IpcBytesReceiver {
+    type Item = Vec<u8>;
+
+    // ipc-channel has no timed receive for byte channels,
+    // so sleep out the timeout between two polls instead
+    fn recv_timeout(&self, timeout: Duration) -> Result<Vec<u8>, TryRecvError> {
+        match self.try_recv() {
+            Err(TryRecvError::Empty) => {
+                thread::sleep(timeout);
+                self.try_recv()
+            },
+            res => res
+        }
+    }
+}
+
+impl RawSender for IpcBytesSender {
+    type Item = Vec<u8>;
+    type Error = io::Error;
+
+    #[inline]
+    fn send(&self, data: Vec<u8>) -> io::Result<()> {
+        IpcBytesSender::send(self, &data)
+    }
+}
+
+pub struct AsyncIpcBytesSender {
+    worker: Worker<IpcBytesSender, SendRequest<Vec<u8>, io::Error>>
+}
+
+impl AsyncIpcBytesSender {
+    pub fn new(channel: IpcBytesSender) -> Self {
+        Self { worker: Worker::sender(channel) }
+    }
+
+    pub fn send(&mut self, data: impl Into<Vec<u8>>) -> IpcBytesSendFuture<'_> {
+        IpcBytesSendFuture {
+            receiver: self.worker.send(data.into()),
+            parent: PhantomData,
+        }
+    }
+
+    pub fn stats(&self) -> ChannelStats {
+        self.worker.stats()
+    }
+
+    /// Waits for any send in progress to finish.
+    ///
+    /// Returns the payloads that were queued by dropped send futures
+    /// but never handed to the underlying [`IpcBytesSender`].
+    pub async fn close(self) -> Vec<Vec<u8>> {
+        self.worker.shutdown().await
+    }
+}
+
+/// An async wrapper around an [`IpcBytesReceiver`].
+///
+/// ipc-channel has no timed receive for byte channels, so a pending receive polls the channel from a pool thread,
+/// backing off from 1ms to 50ms between polls. On a quiet channel a message can take up to 50ms to be picked up,
+/// and like with [`AsyncIpcReceiver`](super::AsyncIpcReceiver) every pending receive holds a pool thread while it waits.
+pub struct AsyncIpcBytesReceiver {
+    core: ReceiverCore<IpcBytesReceiver, Vec<u8>>
+}
+
+impl AsyncIpcBytesReceiver {
+    pub fn new(channel: IpcBytesReceiver) -> Self {
+        Self { core: ReceiverCore::new(channel) }
+    }
+
+    pub fn recv(&self) -> IpcBytesReceiveFuture<'_> {
+        self.core.recv()
+    }
+
+    pub fn stats(&self) -> ChannelStats {
+        self.core.stats()
+    }
+
+    /// Waits for any receive in progress to finish.
+    ///
+    /// Hands back the underlying [`IpcBytesReceiver`] so messages that were not received yet are not lost,
+    /// preceded by any messages that arrived for dropped receive futures and were never handed out.
+    pub async fn close(self) -> (Vec<Vec<u8>>, IpcBytesReceiver) {
+        self.core.close().await
+    }
+}
+
+impl_future! { AsyncIpcBytesSender |> IpcBytesSendFuture |> Result<(), ChannelError<io::Error>> }
+pub type IpcBytesReceiveFuture<'a> = IpcReceiveFuture<'a, Vec<u8>>;
+
+#[cfg(test)]
+mod tests {
+    use ipc_channel::ipc;
+    use super::AsyncIpcBytesReceiver;
+    use crate::async_channels::recv::tests::check_dropped_recv_keeps_its_message;
+
+    #[tokio::test]
+    async fn dropped_recv_keeps_its_message() {
+        let (tx, rx) = ipc::bytes_channel().unwrap();
+        let rx = AsyncIpcBytesReceiver::new(rx);
+        check_dropped_recv_keeps_its_message(&rx.core, b"hello".to_vec(), |message| tx.send(message).unwrap()).await;
+    }
+}
@@ -1,18 +1,57 @@
+mod broadcast;
+mod bytes;
+mod pool;
 mod recv;
 mod send;
+mod server;
 
+pub use broadcast::*;
+pub use bytes::*;
 pub use recv::*;
 pub use send::*;
+pub use server::*;
+
+pub use pool::ChannelStats;
+pub(crate) use pool::{Counters, Served, Worker};
+
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+
+#[derive(Debug)]
+pub enum ChannelError<E> {
+    /// the underlying ipc channel reported an error
+    Ipc(E),
+    /// the worker servicing the request went away before finishing it
+    ChannelDead
+}
+
+impl<E: Display> Display for ChannelError<E> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ChannelError::Ipc(err) => Display::fmt(err, f),
+            ChannelError::ChannelDead => f.write_str("ipc thread died unexpectedly")
+        }
+    }
+}
+
+impl<E: Error + 'static> Error for ChannelError<E> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ChannelError::Ipc(err) => Some(err),
+            ChannelError::ChannelDead => None
+        }
+    }
+}
 
 macro_rules! impl_future {
-    ($parent:ident |> $name:ident |> $ty:ty) => {
+    ($parent:ident $(<$T:ident>)? |> $name:ident |> $ty:ty) => {
         #[must_use = "futures do nothing unless you `.await` or poll them"]
-        pub struct $name<'a, T> {
+        pub struct $name<'a $(, $T)?> {
             receiver: tokio::sync::oneshot::Receiver<$ty>,
-            parent: PhantomData<&'a mut $parent<T>>
+            parent: PhantomData<&'a $parent$(<$T>)?>
         }
 
-        impl<'a, T> Future for $name<'a, T> {
+        impl<'a $(, $T)?> Future for $name<'a $(, $T)?> {
             type Output = $ty;
 
             fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
@@ -21,7 +60,7 @@ macro_rules! impl_future {
                 match Pin::new(&mut this.receiver).poll(cx) {
                     Poll::Ready(res) => match res {
                         Ok(res) => Poll::Ready(res),
-                        Err(_) => unreachable!("ipc thread died unexpectedly")
+                        Err(_) => Poll::Ready(Err($crate::async_channels::ChannelError::ChannelDead))
                     },
                     Poll::Pending => Poll::Pending
                 }
@@ -30,4 +69,69 @@ macro_rules! impl_future {
     };
 }
 
-pub(crate) use impl_future;
\ No newline at end of file
+pub(crate) use impl_future;
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+    use std::sync::Arc;
+    use ipc_channel::ipc;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use super::{AsyncIpcReceiver, AsyncIpcSender, ChannelError};
+
+    /// a payload that panics whenever it holds a zero
+    #[derive(Debug, PartialEq)]
+    struct Bomb(u32);
+
+    impl Serialize for Bomb {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            assert_ne!(self.0, 0, "boom");
+            self.0.serialize(serializer)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for Bomb {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let n = u32::deserialize(deserializer)?;
+            assert_ne!(n, 0, "boom");
+            Ok(Bomb(n))
+        }
+    }
+
+    #[tokio::test]
+    async fn shared_payloads_travel_like_their_contents() {
+        let (tx, rx) = ipc::channel::<Arc<Vec<u32>>>().unwrap();
+        AsyncIpcSender::new(tx).send(Arc::new(vec![1, 2, 3])).await.unwrap();
+        assert_eq!(rx.to_opaque().to::<Vec<u32>>().recv().unwrap(), [1, 2, 3]);
+
+        let (tx, rx) = ipc::channel::<Vec<u32>>().unwrap();
+        tx.send(vec![4, 5]).unwrap();
+        let rx = AsyncIpcReceiver::new(rx.to_opaque().to::<Arc<Vec<u32>>>());
+        assert_eq!(*rx.recv().await.unwrap(), [4, 5]);
+
+        let (tx, rx) = ipc::channel::<Rc<str>>().unwrap();
+        tx.send(Rc::from("shared")).unwrap();
+        assert_eq!(&*rx.to_opaque().to::<String>().recv().unwrap(), "shared");
+    }
+
+    #[tokio::test]
+    async fn panicking_receive_resolves_to_channel_dead() {
+        let (tx, rx) = ipc::channel::<u32>().unwrap();
+        tx.send(0).unwrap();
+        tx.send(1).unwrap();
+
+        let rx = AsyncIpcReceiver::new(rx.to_opaque().to::<Bomb>());
+        assert!(matches!(rx.recv().await, Err(ChannelError::ChannelDead)));
+        assert_eq!(rx.recv().await.unwrap(), Bomb(1));
+    }
+
+    #[tokio::test]
+    async fn panicking_send_resolves_to_channel_dead() {
+        let (tx, rx) = ipc::channel().unwrap();
+
+        let mut tx = AsyncIpcSender::new(tx);
+        assert!(matches!(tx.send(Bomb(0)).await, Err(ChannelError::ChannelDead)));
+        tx.send(Bomb(1)).await.unwrap();
+        assert_eq!(rx.recv().unwrap(), Bomb(1));
+    }
+}
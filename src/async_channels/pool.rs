@@ -0,0 +1,226 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock, PoisonError};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+use crossbeam::channel::{Receiver, Sender, TrySendError};
+
+/// how long an idle pool thread waits for more work before exiting
+const KEEP_ALIVE: Duration = Duration::from_secs(10);
+
+type Job = Box<dyn FnOnce() + Send>;
+
+struct Pool {
+    handoff: Sender<Job>,
+    idle: Receiver<Job>
+}
+
+fn pool() -> &'static Pool {
+    static POOL: OnceLock<Pool> = OnceLock::new();
+
+    POOL.get_or_init(|| {
+        // zero capacity, so a handoff only succeeds if a thread is sitting idle waiting for it
+        let (handoff, idle) = crossbeam::channel::bounded(0);
+        Pool { handoff, idle }
+    })
+}
+
+/// runs `job` on the shared pool, only spawning a new thread if none are idle
+pub(crate) fn execute(job: impl FnOnce() + Send + 'static) {
+    let job: Job = Box::new(job);
+
+    if let Err(TrySendError::Full(job) | TrySendError::Disconnected(job)) = pool().handoff.try_send(job) {
+        let idle = pool().idle.clone();
+        // named so the pool's threads are easy to pick out in debuggers and profilers,
+        // short enough to survive the 15 byte limit on linux thread names
+        thread::Builder::new().name("ilgda-worker".into()).spawn(move || {
+            let mut job = job;
+            loop {
+                job();
+                match idle.recv_timeout(KEEP_ALIVE) {
+                    Ok(next) => job = next,
+                    Err(_) => break
+                }
+            }
+        }).expect("failed to spawn thread");
+    }
+}
+
+/// what became of a request once it was served
+pub(crate) enum Served {
+    Completed,
+    Failed,
+    /// the future waiting on the request was dropped before it could complete
+    Cancelled
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ChannelStats {
+    /// messages successfully sent or received,
+    /// a message handed to a receive future that was dropped before returning it only counts once received again
+    pub messages: u64,
+    /// requests the underlying channel failed, e.g. because a message could not be (de)serialized
+    pub failures: u64,
+    /// total time completed and failed requests spent queued behind earlier requests,
+    /// requests whose futures were dropped first aren't included
+    pub queue_wait: Duration
+}
+
+#[derive(Default)]
+pub(crate) struct Counters {
+    messages: AtomicU64,
+    failures: AtomicU64,
+    queue_wait_nanos: AtomicU64
+}
+
+impl Counters {
+    fn count(&self, served: Served, waited: Duration) {
+        match served {
+            Served::Completed => self.messages.fetch_add(1, Ordering::Relaxed),
+            Served::Failed => self.failures.fetch_add(1, Ordering::Relaxed),
+            Served::Cancelled => return
+        };
+
+        let waited = u64::try_from(waited.as_nanos()).unwrap_or(u64::MAX);
+        self.queue_wait_nanos.fetch_add(waited, Ordering::Relaxed);
+    }
+
+    /// takes back a received message, it gets counted again once it is handed out for good
+    pub(crate) fn uncount_message(&self) {
+        self.messages.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+struct State<R, Q> {
+    // None while a job on the pool is serving requests with it
+    resource: Option<R>,
+    requests: VecDeque<(Instant, Q)>,
+    on_release: Option<tokio::sync::oneshot::Sender<R>>
+}
+
+struct Shared<R, Q> {
+    state: Mutex<State<R, Q>>,
+    serve: fn(&mut R, Q) -> Served,
+    counters: Counters
+}
+
+impl<R, Q> Shared<R, Q> {
+    fn lock(&self) -> MutexGuard<'_, State<R, Q>> {
+        // serve never runs with the lock held, so there is nothing to poison
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// serves requests against a resource on the shared pool, one at a time and in order,
+/// so the resource only occupies a thread while it has requests queued
+pub(crate) struct Worker<R, Q> {
+    shared: Arc<Shared<R, Q>>
+}
+
+/// hands the resource back to the worker once a job is done with it,
+/// even if serving a request panicked
+struct Lease<R: Send + 'static, Q: Send + 'static> {
+    shared: Arc<Shared<R, Q>>,
+    resource: Option<R>
+}
+
+impl<R, Q> State<R, Q> {
+    fn release(&mut self, resource: R) {
+        match self.on_release.take() {
+            Some(on_release) => { let _ = on_release.send(resource); }
+            None => self.resource = Some(resource)
+        }
+    }
+}
+
+impl<R: Send + 'static, Q: Send + 'static> Drop for Lease<R, Q> {
+    fn drop(&mut self) {
+        let Some(resource) = self.resource.take() else { return };
+
+        let mut state = self.shared.lock();
+        // serving a request panicked and took the job down with it,
+        // keep going on a fresh job so the requests queued behind it don't hang
+        if thread::panicking() && !state.requests.is_empty() {
+            drop(state);
+            let lease = Lease { shared: Arc::clone(&self.shared), resource: Some(resource) };
+            execute(move || Worker::drive(lease));
+            return
+        }
+
+        state.release(resource);
+    }
+}
+
+impl<R: Send + 'static, Q: Send + 'static> Worker<R, Q> {
+    pub(crate) fn new(resource: R, serve: fn(&mut R, Q) -> Served) -> Self {
+        let state = State { resource: Some(resource), requests: VecDeque::new(), on_release: None };
+        Self { shared: Arc::new(Shared { state: Mutex::new(state), serve, counters: Counters::default() }) }
+    }
+
+    pub(crate) fn counters(&self) -> &Counters {
+        &self.shared.counters
+    }
+
+    pub(crate) fn stats(&self) -> ChannelStats {
+        let counters = &self.shared.counters;
+        ChannelStats {
+            messages: counters.messages.load(Ordering::Relaxed),
+            failures: counters.failures.load(Ordering::Relaxed),
+            queue_wait: Duration::from_nanos(counters.queue_wait_nanos.load(Ordering::Relaxed))
+        }
+    }
+
+    pub(crate) fn submit(&self, request: Q) {
+        let mut state = self.shared.lock();
+        state.requests.push_back((Instant::now(), request));
+
+        if let Some(resource) = state.resource.take() {
+            drop(state);
+
+            let lease = Lease { shared: Arc::clone(&self.shared), resource: Some(resource) };
+            execute(move || Self::drive(lease));
+        }
+    }
+
+    fn drive(mut lease: Lease<R, Q>) {
+        loop {
+            let mut state = lease.shared.lock();
+            let Some((queued_at, request)) = state.requests.pop_front() else {
+                // release the resource while still holding the lock,
+                // so a concurrent submit can't miss that we are done
+                if let Some(resource) = lease.resource.take() {
+                    state.release(resource);
+                }
+                return
+            };
+            drop(state);
+
+            if let Some(resource) = &mut lease.resource {
+                let waited = queued_at.elapsed();
+                lease.shared.counters.count((lease.shared.serve)(resource, request), waited);
+            }
+        }
+    }
+
+    /// takes back the resource once any request being served finishes,
+    /// along with the requests that never got served
+    pub(crate) async fn close(self) -> (R, Vec<Q>) {
+        let (released, unserved) = {
+            let mut state = self.shared.lock();
+            let unserved = state.requests.drain(..).map(|(_, request)| request).collect();
+
+            if let Some(resource) = state.resource.take() {
+                return (resource, unserved)
+            }
+
+            let (on_release, released) = tokio::sync::oneshot::channel();
+            state.on_release = Some(on_release);
+            (released, unserved)
+        };
+
+        // a lease always hands the resource back when it is dropped,
+        // even if the job holding it never got to run
+        let resource = released.await.expect("worker lease dropped without releasing its resource");
+        (resource, unserved)
+    }
+}
@@ -1,77 +1,307 @@
+use std::collections::VecDeque;
 use std::future::Future;
-use std::marker::PhantomData;
 use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
 use std::task::{Context, Poll};
-use std::thread;
-use crossbeam::channel::TrySendError;
+use std::time::Duration;
 use ipc_channel::ipc::{IpcError, IpcReceiver, TryRecvError};
 use serde::{Deserialize, Serialize};
-use super::impl_future;
+use super::{ChannelError, ChannelStats, Counters, Served, Worker};
 
-enum ReceiverMessage<T> {
-    Receive(tokio::sync::oneshot::Sender<Result<T, IpcError>>),
-    Shutdown
-}
+/// how long the worker blocks on the ipc channel before checking
+/// whether the future waiting on it has been dropped
+const CANCELLATION_CHECK_INTERVAL: Duration = Duration::from_millis(50);
 
-pub struct AsyncIpcReceiver<T> {
-    sender: crossbeam::channel::Sender<ReceiverMessage<T>>,
+/// the first wait of a receive, doubled on every empty wait up to [`CANCELLATION_CHECK_INTERVAL`],
+/// so channels that can only be polled stay responsive without waking up constantly
+const INITIAL_WAIT: Duration = Duration::from_millis(1);
+
+/// the receiving half of an ipc channel, as driven by a receiver [`Worker`]
+pub(crate) trait RawReceiver: Send + 'static {
+    type Item: Send + 'static;
+
+    /// waits for up to `timeout` for the next message
+    fn recv_timeout(&self, timeout: Duration) -> Result<Self::Item, TryRecvError>;
 }
 
-impl<T> AsyncIpcReceiver<T>
+impl<T> RawReceiver for IpcReceiver<T>
     where T: 'static + Send + for<'de> Deserialize<'de> + Serialize
 {
-    pub fn new(channel: IpcReceiver<T>) -> Self {
-        let (sender, receiver) =
-            // this should be enough for us to handle
-            // any outgoing future we have, plus 1 slot for if the thread isn't currently receiving
-            // and drop cant run at the same time as another future
-            crossbeam::channel::bounded(1);
-
-        thread::spawn(move || {
-            while let Ok(ReceiverMessage::Receive(send)) = receiver.recv() {
-                while !send.is_closed() {
-                    match channel.try_recv() {
-                        Ok(t) => {
-                            let _ = send.send(Ok(t)); break
-                        }
-                        Err(t) => match t {
-                            TryRecvError::Empty => continue,
-                            TryRecvError::IpcError(e) => {
-                                let _ = send.send(Err(e)); break
-                            }
-                        }
-                    }
+    type Item = T;
+
+    #[inline]
+    fn recv_timeout(&self, timeout: Duration) -> Result<T, TryRecvError> {
+        self.try_recv_timeout(timeout)
+    }
+}
+
+type ReceiveRequest<T> = tokio::sync::oneshot::Sender<Result<T, ChannelError<IpcError>>>;
+
+/// messages that were received for futures dropped before they got them,
+/// handed out to the next receive ahead of anything still in the channel
+pub(crate) struct Stash<T>(Mutex<VecDeque<T>>);
+
+impl<T> Stash<T> {
+    fn lock(&self) -> MutexGuard<'_, VecDeque<T>> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+pub(crate) struct Inbox<R, T> {
+    channel: R,
+    stash: Arc<Stash<T>>
+}
+
+fn deliver<T>(stash: &Stash<T>, send: ReceiveRequest<T>, t: T) -> Served {
+    match send.send(Ok(t)) {
+        Ok(()) => Served::Completed,
+        // the future got dropped before it could take the message,
+        // hold on to it so the next recv gets it instead
+        Err(res) => {
+            if let Ok(t) = res {
+                stash.lock().push_front(t);
+            }
+            Served::Cancelled
+        }
+    }
+}
+
+fn serve<R: RawReceiver>(inbox: &mut Inbox<R, R::Item>, send: ReceiveRequest<R::Item>) -> Served {
+    // block in the kernel until data arrives instead of spinning on try_recv,
+    // only waking up periodically to notice a cancelled future,
+    // or a message restashed by one dropped after we started waiting
+    let mut wait = INITIAL_WAIT;
+    while !send.is_closed() {
+        let stashed = inbox.stash.lock().pop_front();
+        if let Some(t) = stashed {
+            return deliver(&inbox.stash, send, t)
+        }
+
+        match inbox.channel.recv_timeout(wait) {
+            Ok(t) => return deliver(&inbox.stash, send, t),
+            Err(t) => match t {
+                TryRecvError::Empty => wait = (wait * 2).min(CANCELLATION_CHECK_INTERVAL),
+                TryRecvError::IpcError(e) => {
+                    let _ = send.send(Err(ChannelError::Ipc(e)));
+                    return Served::Failed
                 }
             }
-        });
+        }
+    }
 
+    Served::Cancelled
+}
 
-        Self { sender }
+/// the machinery shared by every async receiver, regardless of what it receives
+pub(crate) struct ReceiverCore<R, T> {
+    worker: Worker<Inbox<R, T>, ReceiveRequest<T>>,
+    stash: Arc<Stash<T>>
+}
+
+impl<R: RawReceiver> ReceiverCore<R, R::Item> {
+    pub(crate) fn new(channel: R) -> Self {
+        let stash = Arc::new(Stash(Mutex::new(VecDeque::new())));
+
+        // any number of tasks can be waiting on us at once,
+        // each request is served in order and gets exactly one message
+        let inbox = Inbox { channel, stash: Arc::clone(&stash) };
+        Self { worker: Worker::new(inbox, serve::<R>), stash }
     }
 
-    pub fn recv(&mut self) -> IpcReceiveFuture<T> {
+    pub(crate) fn recv(&self) -> IpcReceiveFuture<'_, R::Item> {
         let (value_sender, value_receiver) = tokio::sync::oneshot::channel();
-
-        match self.sender.try_send(ReceiverMessage::Receive(value_sender)) {
-            Ok(()) => {}
-            Err(TrySendError::Full(_)) => unreachable!(
-                "the previous future needs to have been completed \
-                or else we cant borrow our selves again"
-            ),
-            Err(TrySendError::Disconnected(_)) => unreachable!("ipc thread died unexpectedly")
-        }
+        self.worker.submit(value_sender);
 
         IpcReceiveFuture {
             receiver: value_receiver,
-            parent: PhantomData,
+            stash: &self.stash,
+            counters: self.worker.counters()
+        }
+    }
+
+    pub(crate) fn stats(&self) -> ChannelStats {
+        self.worker.stats()
+    }
+
+    /// waits for the receive in progress to finish,
+    /// handing back the channel along with any messages stashed ahead of it
+    pub(crate) async fn close(self) -> (Vec<R::Item>, R) {
+        let (Inbox { channel, .. }, _) = self.worker.close().await;
+        let stash = self.stash.lock().drain(..).collect();
+        (stash, channel)
+    }
+}
+
+/// An async wrapper around an [`IpcReceiver`].
+///
+/// Messages are only taken off the channel once a receive asks for one, so a sender that gets ahead
+/// still runs into the channel's own buffer limits, and a slow `Deserialize` only holds up this receiver.
+/// In exchange, every pending receive occupies a thread from the shared pool until its message arrives,
+/// or until it notices it was dropped, which takes up to 50ms.
+///
+/// Dropping the receiver closes the channel once no receive is in progress anymore,
+/// so the sending side sees it hang up instead of queueing messages nobody will read.
+pub struct AsyncIpcReceiver<T> {
+    core: ReceiverCore<IpcReceiver<T>, T>
+}
+
+impl<T> AsyncIpcReceiver<T>
+    where T: 'static + Send + for<'de> Deserialize<'de> + Serialize
+{
+    pub fn new(channel: IpcReceiver<T>) -> Self {
+        Self { core: ReceiverCore::new(channel) }
+    }
+
+    pub fn recv(&self) -> IpcReceiveFuture<'_, T> {
+        self.core.recv()
+    }
+
+    pub fn stats(&self) -> ChannelStats {
+        self.core.stats()
+    }
+
+    /// Waits for any receive in progress to finish.
+    ///
+    /// Hands back the underlying [`IpcReceiver`] so messages that were not received yet are not lost,
+    /// preceded by any messages that arrived for dropped receive futures and were never handed out.
+    pub async fn close(self) -> (Vec<T>, IpcReceiver<T>) {
+        self.core.close().await
+    }
+}
+
+/// Resolves to the next message on the channel.
+///
+/// Dropping it before it completes never loses a message,
+/// anything that was already received for it goes to the next receive instead.
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct IpcReceiveFuture<'a, T> {
+    receiver: tokio::sync::oneshot::Receiver<Result<T, ChannelError<IpcError>>>,
+    stash: &'a Stash<T>,
+    counters: &'a Counters
+}
+
+impl<'a, T> Future for IpcReceiveFuture<'a, T> {
+    type Output = Result<T, ChannelError<IpcError>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = Pin::into_inner(self);
+
+        match Pin::new(&mut this.receiver).poll(cx) {
+            Poll::Ready(res) => match res {
+                Ok(res) => Poll::Ready(res),
+                Err(_) => Poll::Ready(Err(ChannelError::ChannelDead))
+            },
+            Poll::Pending => Poll::Pending
         }
     }
 }
 
-impl<T> Drop for AsyncIpcReceiver<T> {
+impl<'a, T> Drop for IpcReceiveFuture<'a, T> {
     fn drop(&mut self) {
-        let _ = self.sender.try_send(ReceiverMessage::Shutdown);
+        // stop the worker from handing us anything else,
+        // then put back whatever it managed to hand us since we were last polled
+        self.receiver.close();
+        if let Ok(Ok(t)) = self.receiver.try_recv() {
+            self.counters.uncount_message();
+            self.stash.lock().push_front(t);
+        }
     }
 }
 
-impl_future! { AsyncIpcReceiver |> IpcReceiveFuture |> Result<T, IpcError> }
\ No newline at end of file
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::fmt::Debug;
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+    use ipc_channel::ipc;
+    use super::{AsyncIpcReceiver, RawReceiver, ReceiverCore, CANCELLATION_CHECK_INTERVAL};
+
+    /// hands `message` to a receive future that is dropped without being polled,
+    /// then checks that the receive queued behind it gets it instead
+    pub(crate) async fn check_dropped_recv_keeps_its_message<R: RawReceiver>(
+        core: &ReceiverCore<R, R::Item>,
+        message: R::Item,
+        send: impl FnOnce(&R::Item)
+    ) where R::Item: PartialEq + Debug {
+        let first = core.recv();
+        // already waiting on the channel by the time first gets dropped
+        let second = core.recv();
+        send(&message);
+
+        // the worker counts the message once it has handed it over
+        while core.stats().messages == 0 {
+            tokio::task::yield_now().await;
+        }
+        drop(first);
+        // it only counts once it is received for good
+        assert_eq!(core.stats().messages, 0);
+
+        let received = tokio::time::timeout(Duration::from_secs(5), second).await;
+        assert_eq!(received.expect("message was lost").unwrap(), message);
+    }
+
+    #[tokio::test]
+    async fn close_hands_back_unreceived_messages() {
+        let (tx, rx) = ipc::channel().unwrap();
+        for i in 0..3u32 {
+            tx.send(i).unwrap();
+        }
+
+        let rx = AsyncIpcReceiver::new(rx);
+        assert_eq!(rx.recv().await.unwrap(), 0);
+        // a cancelled receive must not swallow anything either
+        drop(rx.recv());
+
+        let (stashed, rx) = rx.close().await;
+        assert!(stashed.is_empty());
+        assert_eq!(rx.recv().unwrap(), 1);
+        assert_eq!(rx.recv().unwrap(), 2);
+    }
+
+    #[tokio::test]
+    async fn shared_receiver_delivers_each_message_once() {
+        let (tx, rx) = ipc::channel().unwrap();
+        let rx = Arc::new(AsyncIpcReceiver::new(rx));
+
+        let waiters: Vec<_> = (0..8)
+            .map(|_| {
+                let rx = Arc::clone(&rx);
+                tokio::spawn(async move { rx.recv().await.unwrap() })
+            })
+            .collect();
+
+        for i in 0..8u32 {
+            tx.send(i).unwrap();
+        }
+
+        let mut received = vec![];
+        for waiter in waiters {
+            received.push(waiter.await.unwrap());
+        }
+        received.sort_unstable();
+        assert_eq!(received, (0..8).collect::<Vec<_>>());
+    }
+
+    #[tokio::test]
+    async fn dropping_the_receiver_hangs_up() {
+        let (tx, rx) = ipc::channel::<u32>().unwrap();
+        let rx = AsyncIpcReceiver::new(rx);
+
+        // leave the channel out on the pool, the job gives it up once it notices the cancelled receive
+        drop(rx.recv());
+        drop(rx);
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while tx.send(0).is_ok() {
+            assert!(Instant::now() < deadline, "the channel outlived its receiver");
+            tokio::time::sleep(CANCELLATION_CHECK_INTERVAL).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn dropped_recv_keeps_its_message() {
+        let (tx, rx) = ipc::channel::<u32>().unwrap();
+        let rx = AsyncIpcReceiver::new(rx);
+        check_dropped_recv_keeps_its_message(&rx.core, 1, |&message| tx.send(message).unwrap()).await;
+    }
+}
@@ -2,63 +2,124 @@ use std::future::Future;
 use std::marker::PhantomData;
 use std::pin::Pin;
 use std::task::{Context, Poll};
-use std::thread;
-use crossbeam::channel::TrySendError;
 use ipc_channel::ipc::IpcSender;
 use serde::{Deserialize, Serialize};
-use super::impl_future;
+use super::{impl_future, ChannelError, ChannelStats, Served, Worker};
 
-enum SenderMessage<T> {
-    Send(T, tokio::sync::oneshot::Sender<Result<(), ipc_channel::Error>>),
-    Shutdown
-}
+/// the sending half of an ipc channel, as driven by a sender [`Worker`]
+pub(crate) trait RawSender: Send + 'static {
+    type Item: Send + 'static;
+    type Error: Send + 'static;
 
-pub struct AsyncIpcSender<T> {
-    sender: crossbeam::channel::Sender<SenderMessage<T>>,
+    fn send(&self, data: Self::Item) -> Result<(), Self::Error>;
 }
 
-impl<T> AsyncIpcSender<T>
+impl<T> RawSender for IpcSender<T>
     where T: 'static + Send + for<'de> Deserialize<'de> + Serialize
 {
-    pub fn new(channel: IpcSender<T>) -> Self {
-        let (sender, receiver) =
-            // this should be enough for, shutdown
-            // and any outgoing future we have
-            crossbeam::channel::bounded(2);
-
-        thread::spawn(move || {
-            while let Ok(SenderMessage::Send(data, result_send)) = receiver.recv() {
-                let _ = result_send.send(channel.send(data));
-            }
-        });
+    type Item = T;
+    type Error = ipc_channel::Error;
 
-        Self { sender }
+    #[inline]
+    fn send(&self, data: T) -> Result<(), ipc_channel::Error> {
+        IpcSender::send(self, data)
     }
+}
+
+pub(crate) struct SendRequest<T, E>(T, tokio::sync::oneshot::Sender<Result<(), ChannelError<E>>>);
 
-    pub fn send(&mut self, data: T) -> IpcSendFuture<T> {
+impl<S: RawSender> Worker<S, SendRequest<S::Item, S::Error>> {
+    pub(crate) fn sender(channel: S) -> Self {
+        Worker::new(channel, |channel, SendRequest(data, result_send)| {
+            let result = channel.send(data);
+            let served = if result.is_ok() { Served::Completed } else { Served::Failed };
+
+            let _ = result_send.send(result.map_err(ChannelError::Ipc));
+            served
+        })
+    }
+
+    pub(crate) fn send(&self, data: S::Item) -> tokio::sync::oneshot::Receiver<Result<(), ChannelError<S::Error>>> {
         let (result_sender, result_receiver) = tokio::sync::oneshot::channel();
+        self.submit(SendRequest(data, result_sender));
+        result_receiver
+    }
 
-        match self.sender.try_send(SenderMessage::Send(data, result_sender)) {
-            Ok(()) => {}
-            Err(TrySendError::Full(_)) => unreachable!(
-                "the previous future needs to have been completed \
-                or else we cant borrow our selves again"
-            ),
-            Err(TrySendError::Disconnected(_)) => unreachable!("ipc thread died unexpectedly")
-        }
+    /// waits for the send in progress to finish,
+    /// returning whatever was queued behind it
+    pub(crate) async fn shutdown(self) -> Vec<S::Item> {
+        let (_, unsent) = self.close().await;
+        unsent.into_iter().map(|SendRequest(data, _)| data).collect()
+    }
+}
 
+pub struct AsyncIpcSender<T> {
+    worker: Worker<IpcSender<T>, SendRequest<T, ipc_channel::Error>>
+}
+
+impl<T> AsyncIpcSender<T>
+    where T: 'static + Send + for<'de> Deserialize<'de> + Serialize
+{
+    pub fn new(channel: IpcSender<T>) -> Self {
+        Self { worker: Worker::sender(channel) }
+    }
+
+    pub fn send(&mut self, data: T) -> IpcSendFuture<'_, T> {
         IpcSendFuture {
-            receiver: result_receiver,
+            receiver: self.worker.send(data),
             parent: PhantomData,
         }
     }
-}
 
+    pub fn stats(&self) -> ChannelStats {
+        self.worker.stats()
+    }
 
-impl<T> Drop for AsyncIpcSender<T> {
-    fn drop(&mut self) {
-        let _ = self.sender.try_send(SenderMessage::Shutdown);
+    /// Waits for any send in progress to finish.
+    ///
+    /// Returns the messages that were queued by dropped send futures
+    /// but never handed to the underlying [`IpcSender`].
+    pub async fn close(self) -> Vec<T> {
+        self.worker.shutdown().await
     }
 }
 
-impl_future! { AsyncIpcSender |> IpcSendFuture |> Result<(), ipc_channel::Error> }
\ No newline at end of file
+impl_future! { AsyncIpcSender<T> |> IpcSendFuture |> Result<(), ChannelError<ipc_channel::Error>> }
+
+#[cfg(test)]
+mod tests {
+    use std::future::{poll_fn, Future};
+    use std::pin::pin;
+    use std::task::Poll;
+    use std::thread;
+    use ipc_channel::ipc;
+    use super::AsyncIpcSender;
+
+    #[tokio::test]
+    async fn close_returns_unsent_messages() {
+        let (tx, rx) = ipc::channel::<Vec<u8>>().unwrap();
+        let mut tx = AsyncIpcSender::new(tx);
+
+        // nobody is reading yet, so the worker gets stuck on this one
+        drop(tx.send(vec![0; 16 << 20]));
+        drop(tx.send(vec![1]));
+
+        let mut close = pin!(tx.close());
+        let first_poll = poll_fn(|cx| Poll::Ready(close.as_mut().poll(cx).is_pending())).await;
+        assert!(first_poll);
+
+        let reader = thread::spawn(move || {
+            let mut received = vec![];
+            while let Ok(msg) = rx.recv() {
+                received.push(msg);
+            }
+            received
+        });
+
+        let unsent = close.await;
+        let received = reader.join().unwrap();
+
+        assert_eq!(unsent.last(), Some(&vec![1]));
+        assert_eq!(received.len() + unsent.len(), 2);
+    }
+}
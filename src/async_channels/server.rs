@@ -0,0 +1,62 @@
+use std::io;
+use std::thread;
+use ipc_channel::ipc::IpcOneShotServer;
+use serde::{Deserialize, Serialize};
+use super::{AsyncIpcReceiver, ChannelError};
+
+pub struct AsyncIpcOneShotServer<T> {
+    server: IpcOneShotServer<T>
+}
+
+impl<T> AsyncIpcOneShotServer<T>
+    where T: 'static + Send + for<'de> Deserialize<'de> + Serialize
+{
+    /// Creates the server, along with the name a client can connect to it by.
+    pub fn new() -> io::Result<(Self, String)> {
+        let (server, name) = IpcOneShotServer::new()?;
+        Ok((Self { server }, name))
+    }
+
+    /// Waits for a client to connect and send its first message,
+    /// returning the rest of the channel wrapped in an [`AsyncIpcReceiver`].
+    ///
+    /// The accept itself runs on a dedicated thread,
+    /// which stays blocked until a client connects even if this future is dropped.
+    pub async fn accept(self) -> Result<(AsyncIpcReceiver<T>, T), ChannelError<ipc_channel::Error>> {
+        let (sender, receiver) = tokio::sync::oneshot::channel();
+
+        let server = self.server;
+        thread::Builder::new().name("ilgda-accept".into()).spawn(move || {
+            let _ = sender.send(server.accept());
+        }).expect("failed to spawn thread");
+
+        match receiver.await {
+            Ok(Ok((channel, first))) => Ok((AsyncIpcReceiver::new(channel), first)),
+            Ok(Err(err)) => Err(ChannelError::Ipc(err)),
+            Err(_) => Err(ChannelError::ChannelDead)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+    use ipc_channel::ipc::IpcSender;
+    use super::AsyncIpcOneShotServer;
+
+    #[tokio::test]
+    async fn accept_hands_over_the_first_message_and_the_channel() {
+        let (server, name) = AsyncIpcOneShotServer::<String>::new().unwrap();
+
+        let client = thread::spawn(move || {
+            let tx = IpcSender::connect(name).unwrap();
+            tx.send("hello".to_owned()).unwrap();
+            tx.send("again".to_owned()).unwrap();
+        });
+
+        let (rx, first) = server.accept().await.unwrap();
+        assert_eq!(first, "hello");
+        assert_eq!(rx.recv().await.unwrap(), "again");
+        client.join().unwrap();
+    }
+}
@@ -0,0 +1,49 @@
+use std::borrow::Cow;
+
+use super::IlgdaId;
+
+/// A view of an [`IlgdaId`] that can borrow its string and byte contents.
+///
+/// Deserializing this instead of an [`IlgdaId`] lets zero-copy deserializers
+/// (e.g. `serde_json::from_slice`) hand out ids without copying them;
+/// it serializes to, and orders, exactly like the [`IlgdaId`] it stands for.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum IlgdaIdRef<'a> {
+    Numeric(u64),
+    String(Cow<'a, str>),
+    Bytes(Cow<'a, [u8]>),
+    Uuid([u8; 16]),
+    Composite(Vec<IlgdaIdRef<'a>>)
+}
+
+impl IlgdaIdRef<'_> {
+    /// Copies whatever is borrowed into an owned [`IlgdaId`]
+    #[inline]
+    pub fn into_owned(self) -> IlgdaId {
+        IlgdaId::from(self)
+    }
+}
+
+impl From<IlgdaIdRef<'_>> for IlgdaId {
+    fn from(value: IlgdaIdRef<'_>) -> Self {
+        match value {
+            IlgdaIdRef::Numeric(num) => IlgdaId::Numeric(num),
+            IlgdaIdRef::String(str) => IlgdaId::from(str.into_owned()),
+            IlgdaIdRef::Bytes(bytes) => IlgdaId::from(bytes.into_owned()),
+            IlgdaIdRef::Uuid(bytes) => IlgdaId::Uuid(bytes),
+            IlgdaIdRef::Composite(ids) => IlgdaId::from(ids.into_iter().map(IlgdaId::from).collect::<Vec<_>>())
+        }
+    }
+}
+
+impl<'a> From<&'a IlgdaId> for IlgdaIdRef<'a> {
+    fn from(value: &'a IlgdaId) -> Self {
+        match value {
+            IlgdaId::Numeric(num) => IlgdaIdRef::Numeric(*num),
+            IlgdaId::String(str) => IlgdaIdRef::String(Cow::Borrowed(str)),
+            IlgdaId::Bytes(bytes) => IlgdaIdRef::Bytes(Cow::Borrowed(bytes)),
+            IlgdaId::Uuid(bytes) => IlgdaIdRef::Uuid(*bytes),
+            IlgdaId::Composite(ids) => IlgdaIdRef::Composite(ids.iter().map(IlgdaIdRef::from).collect())
+        }
+    }
+}
@@ -0,0 +1,473 @@
+use std::{
+    collections::HashMap,
+    io::{self, ErrorKind}
+};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use heap_array::HeapArray;
+
+use super::{IlgdaId, MAX_NESTING};
+
+const NUMERIC: u8 = 0;
+const STRING: u8 = 1;
+const BYTES: u8 = 2;
+const UUID: u8 = 3;
+const COMPOSITE: u8 = 4;
+// only valid in streams written by an IdInterner
+const DEFINE: u8 = 5;
+const REFERENCE: u8 = 6;
+
+// caps how much is allocated up front for a length read off the wire
+const MAX_PREALLOCATION: usize = 4096;
+
+const DEFAULT_INTERN_LIMIT: usize = 1024;
+
+fn invalid_data(msg: &'static str) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, msg)
+}
+
+fn write_varint(buf: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buf.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+fn write_len_prefixed(buf: &mut Vec<u8>, bytes: &[u8]) {
+    write_varint(buf, bytes.len() as u64);
+    buf.extend_from_slice(bytes);
+}
+
+async fn read_varint<R: AsyncRead + Unpin + ?Sized>(reader: &mut R) -> io::Result<u64> {
+    let mut value = 0_u64;
+    for shift in (0..64).step_by(7) {
+        let byte = reader.read_u8().await?;
+        let bits = u64::from(byte & 0x7f);
+        if bits << shift >> shift != bits {
+            return Err(invalid_data("varint overflows a u64"))
+        }
+
+        value |= bits << shift;
+        if byte & 0x80 == 0 { return Ok(value) }
+    }
+
+    Err(invalid_data("varint overflows a u64"))
+}
+
+async fn read_len_prefixed<R: AsyncRead + Unpin + ?Sized>(reader: &mut R) -> io::Result<Vec<u8>> {
+    let len = read_varint(reader).await?;
+    let mut buf = Vec::with_capacity((len as usize).min(MAX_PREALLOCATION));
+    reader.take(len).read_to_end(&mut buf).await?;
+    match buf.len() as u64 == len {
+        true => Ok(buf),
+        false => Err(ErrorKind::UnexpectedEof.into())
+    }
+}
+
+async fn read_str<R: AsyncRead + Unpin + ?Sized>(reader: &mut R) -> io::Result<Box<str>> {
+    let bytes = read_len_prefixed(reader).await?;
+    match String::from_utf8(bytes) {
+        Ok(str) => Ok(str.into_boxed_str()),
+        Err(_) => Err(invalid_data("string id isn't valid utf-8"))
+    }
+}
+
+impl IlgdaId {
+    fn encode_into(&self, buf: &mut Vec<u8>, mut interner: Option<&mut IdInterner>) {
+        match self {
+            IlgdaId::Numeric(num) => {
+                buf.push(NUMERIC);
+                write_varint(buf, *num)
+            },
+            IlgdaId::String(str) => match interner {
+                Some(interner) => interner.write_str(buf, str),
+                None => {
+                    buf.push(STRING);
+                    write_len_prefixed(buf, str.as_bytes())
+                }
+            },
+            IlgdaId::Bytes(bytes) => {
+                buf.push(BYTES);
+                write_len_prefixed(buf, bytes)
+            },
+            IlgdaId::Uuid(bytes) => {
+                buf.push(UUID);
+                buf.extend_from_slice(bytes)
+            },
+            IlgdaId::Composite(ids) => {
+                buf.push(COMPOSITE);
+                write_varint(buf, ids.len() as u64);
+                ids.iter().for_each(|id| id.encode_into(buf, interner.as_deref_mut()))
+            }
+        }
+    }
+
+    /// Writes the id using its compact binary encoding.
+    ///
+    /// Unlike the serde impls, this format doesn't depend on the serializer in use:
+    /// every id starts with a 1 byte tag (`0` numeric, `1` string, `2` bytes, `3` uuid, `4` composite),
+    /// followed by
+    /// - numeric: the number as an unsigned LEB128 varint
+    /// - string, bytes: a varint length, followed by that many bytes (utf-8 for strings)
+    /// - uuid: the 16 raw bytes
+    /// - composite: a varint count, followed by that many encoded ids
+    pub async fn encode_to<W: AsyncWrite + Unpin + ?Sized>(&self, writer: &mut W) -> io::Result<()> {
+        let mut buf = vec![];
+        self.encode_into(&mut buf, None);
+        writer.write_all(&buf).await
+    }
+
+    /// Reads an id written by [`IlgdaId::encode_to`]
+    #[inline]
+    pub async fn decode_from<R: AsyncRead + Unpin + ?Sized>(reader: &mut R) -> io::Result<IlgdaId> {
+        Self::decode(reader, None).await
+    }
+
+    async fn decode<R: AsyncRead + Unpin + ?Sized>(reader: &mut R, mut resolver: Option<&mut IdResolver>) -> io::Result<IlgdaId> {
+        // composites still being filled in, along with how many ids each is missing
+        let mut open: Vec<(Vec<IlgdaId>, u64)> = vec![];
+
+        loop {
+            let mut id = match reader.read_u8().await? {
+                NUMERIC => IlgdaId::Numeric(read_varint(reader).await?),
+                STRING => IlgdaId::from(read_str(reader).await?),
+                BYTES => IlgdaId::Bytes(HeapArray::from(read_len_prefixed(reader).await?)),
+                UUID => {
+                    let mut bytes = [0; 16];
+                    reader.read_exact(&mut bytes).await?;
+                    IlgdaId::Uuid(bytes)
+                },
+                COMPOSITE if open.len() == MAX_NESTING => return Err(invalid_data("composite id is nested too deeply")),
+                COMPOSITE => match read_varint(reader).await? {
+                    0 => IlgdaId::Composite(Box::new([])),
+                    len => {
+                        open.push((Vec::with_capacity((len as usize).min(MAX_PREALLOCATION)), len));
+                        continue
+                    }
+                },
+                DEFINE if resolver.is_some() => {
+                    let str = read_str(reader).await?;
+                    resolver.as_deref_mut().unwrap().define(str)?
+                },
+                REFERENCE if resolver.is_some() => {
+                    let handle = read_varint(reader).await?;
+                    resolver.as_deref().unwrap().resolve(handle)?
+                },
+                _ => return Err(invalid_data("unknown id tag"))
+            };
+
+            loop {
+                let Some((ids, missing)) = open.last_mut() else { return Ok(id) };
+
+                ids.push(id);
+                *missing -= 1;
+                if *missing != 0 { break }
+
+                let (ids, _) = open.pop().unwrap();
+                id = IlgdaId::from(ids);
+            }
+        }
+    }
+}
+
+/// The writing half of an interned id stream.
+///
+/// Works like [`IlgdaId::encode_to`], except the first time a string id is written
+/// it gets defined under a handle, and every later occurrence is written as just that handle.
+/// Use one per connection, paired with an [`IdResolver`] on the reading end.
+///
+/// On the wire, a definition is tag `5` followed by the length prefixed string,
+/// and a reference is tag `6` followed by the handle as a varint;
+/// handles count up from `0` in the order strings were defined.
+///
+/// Once `limit` strings have been defined, new strings are written out in full.
+pub struct IdInterner {
+    handles: HashMap<Box<str>, u64>,
+    // defined by the id being encoded, only kept once it was actually written
+    pending: HashMap<Box<str>, u64>,
+    limit: usize
+}
+
+impl IdInterner {
+    #[inline]
+    pub fn new() -> Self {
+        Self::with_limit(DEFAULT_INTERN_LIMIT)
+    }
+
+    #[inline]
+    pub fn with_limit(limit: usize) -> Self {
+        Self { handles: HashMap::new(), pending: HashMap::new(), limit }
+    }
+
+    fn write_str(&mut self, buf: &mut Vec<u8>, str: &str) {
+        if let Some(&handle) = self.handles.get(str).or_else(|| self.pending.get(str)) {
+            buf.push(REFERENCE);
+            write_varint(buf, handle);
+            return
+        }
+
+        let defined = self.handles.len() + self.pending.len();
+        match defined < self.limit {
+            true => {
+                self.pending.insert(Box::from(str), defined as u64);
+                buf.push(DEFINE)
+            },
+            false => buf.push(STRING)
+        }
+        write_len_prefixed(buf, str.as_bytes())
+    }
+
+    /// Writes the id, defining any string ids that weren't yet.
+    ///
+    /// If writing fails the new definitions are forgotten,
+    /// but the reading end may have gotten part of the id, so the stream can't be trusted anymore.
+    pub async fn encode_to<W: AsyncWrite + Unpin + ?Sized>(&mut self, id: &IlgdaId, writer: &mut W) -> io::Result<()> {
+        // left over from a call whose future was dropped before it could finish
+        self.pending.clear();
+
+        let mut buf = vec![];
+        id.encode_into(&mut buf, Some(self));
+        writer.write_all(&buf).await?;
+
+        self.handles.extend(self.pending.drain());
+        Ok(())
+    }
+}
+
+impl Default for IdInterner {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The reading half of an interned id stream, see [`IdInterner`].
+///
+/// `limit` must be at least the limit of the interner on the other end,
+/// defining more strings than that is treated as invalid data.
+pub struct IdResolver {
+    strings: Vec<Box<str>>,
+    limit: usize
+}
+
+impl IdResolver {
+    #[inline]
+    pub fn new() -> Self {
+        Self::with_limit(DEFAULT_INTERN_LIMIT)
+    }
+
+    #[inline]
+    pub fn with_limit(limit: usize) -> Self {
+        Self { strings: vec![], limit }
+    }
+
+    fn define(&mut self, str: Box<str>) -> io::Result<IlgdaId> {
+        if self.strings.len() >= self.limit {
+            return Err(invalid_data("too many interned ids"))
+        }
+
+        self.strings.push(str.clone());
+        Ok(IlgdaId::String(str))
+    }
+
+    fn resolve(&self, handle: u64) -> io::Result<IlgdaId> {
+        match usize::try_from(handle).ok().and_then(|handle| self.strings.get(handle)) {
+            Some(str) => Ok(IlgdaId::String(str.clone())),
+            None => Err(invalid_data("reference to an undefined id"))
+        }
+    }
+
+    pub async fn decode_from<R: AsyncRead + Unpin + ?Sized>(&mut self, reader: &mut R) -> io::Result<IlgdaId> {
+        IlgdaId::decode(reader, Some(self)).await
+    }
+}
+
+impl Default for IdResolver {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, ErrorKind};
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+    use tokio::io::AsyncWrite;
+    use super::{IdInterner, IdResolver, IlgdaId, COMPOSITE, MAX_NESTING, NUMERIC};
+
+    fn nested(depth: usize, innermost: IlgdaId) -> IlgdaId {
+        (0..depth).fold(innermost, |id, _| IlgdaId::from(vec![id]))
+    }
+
+    async fn encode(id: &IlgdaId) -> Vec<u8> {
+        let mut buf = vec![];
+        id.encode_to(&mut buf).await.unwrap();
+        buf
+    }
+
+    async fn decode(mut bytes: &[u8]) -> std::io::Result<IlgdaId> {
+        let id = IlgdaId::decode_from(&mut bytes).await?;
+        assert!(bytes.is_empty(), "{} bytes left over", bytes.len());
+        Ok(id)
+    }
+
+    #[tokio::test]
+    async fn round_trips() {
+        let ids = [
+            IlgdaId::from(0_u64),
+            IlgdaId::from(300_u64),
+            IlgdaId::from(u64::MAX),
+            IlgdaId::from("hello"),
+            IlgdaId::from(""),
+            IlgdaId::from([0xde, 0xad, 0xbe, 0xef]),
+            IlgdaId::from(Vec::<u8>::new()),
+            IlgdaId::Uuid(*b"0123456789abcdef"),
+            IlgdaId::Composite(Box::new([])),
+            IlgdaId::from(vec![IlgdaId::from(1_u64), IlgdaId::from("a"), IlgdaId::Composite(Box::new([]))]),
+            nested(MAX_NESTING, IlgdaId::from(1_u64)),
+            nested(MAX_NESTING - 1, IlgdaId::Composite(Box::new([])))
+        ];
+
+        for id in ids {
+            assert_eq!(decode(&encode(&id).await).await.unwrap(), id);
+        }
+    }
+
+    #[tokio::test]
+    async fn encoding() {
+        assert_eq!(encode(&IlgdaId::from(300_u64)).await, [0, 0xac, 0x02]);
+        assert_eq!(encode(&IlgdaId::from("ab")).await, [1, 2, b'a', b'b']);
+        assert_eq!(encode(&IlgdaId::from([7])).await, [2, 1, 7]);
+        assert_eq!(encode(&IlgdaId::from(vec![IlgdaId::from(1_u64)])).await, [4, 1, 0, 1]);
+    }
+
+    #[tokio::test]
+    async fn overflowing_varints() {
+        let max = [&[NUMERIC][..], &[0xff; 9], &[0x01]].concat();
+        assert_eq!(decode(&max).await.unwrap(), IlgdaId::from(u64::MAX));
+
+        let too_big = [&[NUMERIC][..], &[0xff; 9], &[0x02]].concat();
+        assert_eq!(decode(&too_big).await.unwrap_err().kind(), ErrorKind::InvalidData);
+
+        let too_long = [&[NUMERIC][..], &[0x80; 10], &[0x00]].concat();
+        assert_eq!(decode(&too_long).await.unwrap_err().kind(), ErrorKind::InvalidData);
+    }
+
+    #[tokio::test]
+    async fn nesting_is_limited() {
+        let too_deep = nested(MAX_NESTING, IlgdaId::Composite(Box::new([])));
+        assert_eq!(decode(&encode(&too_deep).await).await.unwrap_err().kind(), ErrorKind::InvalidData);
+
+        let hostile = [COMPOSITE, 1].repeat(100_000);
+        assert_eq!(decode(&hostile).await.unwrap_err().kind(), ErrorKind::InvalidData);
+    }
+
+    #[tokio::test]
+    async fn malformed_input() {
+        // truncated string, composite and uuid
+        for bytes in [&[1, 5, b'a'][..], &[4, 2, 0, 1], &[3, 0, 0]] {
+            assert_eq!(decode(bytes).await.unwrap_err().kind(), ErrorKind::UnexpectedEof);
+        }
+
+        // invalid utf-8, and interner tags outside of an interned stream
+        for bytes in [&[1, 1, 0xff][..], &[5, 1, b'a'], &[6, 0], &[7]] {
+            assert_eq!(decode(bytes).await.unwrap_err().kind(), ErrorKind::InvalidData);
+        }
+    }
+
+    // accepts nothing, as if the connection went away
+    struct Broken;
+
+    impl AsyncWrite for Broken {
+        fn poll_write(self: Pin<&mut Self>, _: &mut Context<'_>, _: &[u8]) -> Poll<io::Result<usize>> {
+            Poll::Ready(Err(ErrorKind::BrokenPipe.into()))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    #[tokio::test]
+    async fn interned_round_trips() {
+        let ids = [
+            IlgdaId::from("a"),
+            IlgdaId::from(vec![IlgdaId::from("a"), IlgdaId::from("b"), IlgdaId::from("b")]),
+            IlgdaId::from(1_u64),
+            IlgdaId::from("b"),
+            IlgdaId::from("c")
+        ];
+
+        let mut interner = IdInterner::new();
+        let mut buf = vec![];
+        for id in &ids {
+            interner.encode_to(id, &mut buf).await.unwrap();
+        }
+
+        // every repeat is just a handle
+        assert_eq!(buf, [
+            5, 1, b'a',
+            4, 3, 6, 0, 5, 1, b'b', 6, 1,
+            0, 1,
+            6, 1,
+            5, 1, b'c'
+        ]);
+
+        let mut resolver = IdResolver::new();
+        let mut reader = &buf[..];
+        for id in ids {
+            assert_eq!(resolver.decode_from(&mut reader).await.unwrap(), id);
+        }
+        assert!(reader.is_empty());
+    }
+
+    #[tokio::test]
+    async fn intern_limit() {
+        let mut interner = IdInterner::with_limit(1);
+        let mut buf = vec![];
+        for id in ["a", "b", "a", "b"] {
+            interner.encode_to(&IlgdaId::from(id), &mut buf).await.unwrap();
+        }
+        assert_eq!(buf, [5, 1, b'a', 1, 1, b'b', 6, 0, 1, 1, b'b']);
+
+        let mut resolver = IdResolver::with_limit(1);
+        let mut reader = &buf[..];
+        for id in ["a", "b", "a", "b"] {
+            assert_eq!(resolver.decode_from(&mut reader).await.unwrap(), IlgdaId::from(id));
+        }
+
+        // a resolver with a smaller limit than the interner rejects the extra definitions
+        let mut resolver = IdResolver::with_limit(0);
+        assert_eq!(resolver.decode_from(&mut &buf[..]).await.unwrap_err().kind(), ErrorKind::InvalidData);
+    }
+
+    #[tokio::test]
+    async fn undefined_references() {
+        let mut resolver = IdResolver::new();
+        for bytes in [&[6, 0][..], &[6, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01]] {
+            assert_eq!(resolver.decode_from(&mut &bytes[..]).await.unwrap_err().kind(), ErrorKind::InvalidData);
+        }
+    }
+
+    #[tokio::test]
+    async fn failed_writes_define_nothing() {
+        let mut interner = IdInterner::new();
+        let id = IlgdaId::from(vec![IlgdaId::from("a"), IlgdaId::from("a")]);
+        assert_eq!(interner.encode_to(&id, &mut Broken).await.unwrap_err().kind(), ErrorKind::BrokenPipe);
+
+        // "a" never made it to the other end, so it still has to be defined
+        let mut buf = vec![];
+        interner.encode_to(&id, &mut buf).await.unwrap();
+        assert_eq!(buf, [4, 2, 5, 1, b'a', 6, 0]);
+
+        let mut resolver = IdResolver::new();
+        assert_eq!(resolver.decode_from(&mut &buf[..]).await.unwrap(), id);
+    }
+}
@@ -0,0 +1,564 @@
+use std::{
+    error::Error,
+    fmt::{self, Debug, Display, Formatter, Write},
+    hash::{Hash, Hasher},
+    mem,
+    num::NonZeroU64,
+    str::FromStr
+};
+use heap_array::HeapArray;
+
+mod borrowed;
+#[cfg(feature = "tokio")]
+mod codec;
+mod serialization;
+
+pub use borrowed::IlgdaIdRef;
+#[cfg(feature = "tokio")]
+pub use codec::{IdInterner, IdResolver};
+
+// how deep composites may nest before parsing, decoding or deserializing gives up
+const MAX_NESTING: usize = 64;
+
+/// An identifier for an entity on the other side of a channel.
+///
+/// Ids of the same kind compare by their contents (numbers numerically, strings and bytes
+/// lexicographically, composites element by element); ids of different kinds are ordered by kind:
+/// `Numeric < String < Bytes < Uuid < Composite`.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum IlgdaId {
+    Numeric(u64),
+    String(Box<str>),
+    Bytes(HeapArray<u8>),
+    Uuid([u8; 16]),
+    Composite(Box<[IlgdaId]>)
+}
+
+macro_rules! impl_from {
+    ($id_ty:ident |> $ty:ty $(|from> $({for $($constraints:tt)*})? $other_ty:ty)*) => {
+        impl From<$ty> for IlgdaId {
+            #[inline(always)]
+            fn from(value: $ty) -> Self {
+                IlgdaId::$id_ty(value)
+            }
+        }
+        $(
+        impl$(<$($constraints)*>)? From<$other_ty> for IlgdaId {
+            #[inline]
+            fn from(value: $other_ty) -> Self {
+                IlgdaId::$id_ty(<$ty>::from(value))
+            }
+        }
+        )*
+    };
+}
+
+impl_from! { Numeric |> u64 |from> u32 |from> u16 |from> u8 |from> NonZeroU64 }
+impl_from! { String  |> Box<str> |from> String |from> &str }
+impl_from! {
+    Bytes|> HeapArray<u8>
+    |from>  Vec<u8>
+    |from>  Box<[u8]>
+    |from>  {for const N: usize} [u8; N]
+    |from>  &[u8]
+}
+impl_from! { Composite |> Box<[IlgdaId]> |from> Vec<IlgdaId> }
+
+#[cfg(feature = "uuid")]
+impl From<uuid::Uuid> for IlgdaId {
+    #[inline]
+    fn from(value: uuid::Uuid) -> Self {
+        IlgdaId::Uuid(value.into_bytes())
+    }
+}
+
+/// The kind of an [`IlgdaId`], without its contents
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IdKind {
+    Numeric,
+    String,
+    Bytes,
+    Uuid,
+    Composite
+}
+
+impl Display for IdKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            IdKind::Numeric => "numeric",
+            IdKind::String => "string",
+            IdKind::Bytes => "bytes",
+            IdKind::Uuid => "uuid",
+            IdKind::Composite => "composite"
+        })
+    }
+}
+
+impl IlgdaId {
+    #[inline]
+    pub fn kind(&self) -> IdKind {
+        match self {
+            IlgdaId::Numeric(_) => IdKind::Numeric,
+            IlgdaId::String(_) => IdKind::String,
+            IlgdaId::Bytes(_) => IdKind::Bytes,
+            IlgdaId::Uuid(_) => IdKind::Uuid,
+            IlgdaId::Composite(_) => IdKind::Composite
+        }
+    }
+}
+
+/// The error returned when converting an [`IlgdaId`] of the wrong kind,
+/// it hands back the id that couldn't be converted
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IdKindMismatch {
+    expected: IdKind,
+    id: IlgdaId
+}
+
+impl IdKindMismatch {
+    #[inline]
+    pub fn expected(&self) -> IdKind {
+        self.expected
+    }
+
+    #[inline]
+    pub fn found(&self) -> IdKind {
+        self.id.kind()
+    }
+
+    #[inline]
+    pub fn into_id(self) -> IlgdaId {
+        self.id
+    }
+}
+
+impl Display for IdKindMismatch {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "expected a {} id, found a {} id", self.expected, self.found())
+    }
+}
+
+impl Error for IdKindMismatch {}
+
+macro_rules! impl_try_into {
+    ($id_ty:ident |> $($ty:ty),+) => {
+        $(
+        impl TryFrom<IlgdaId> for $ty {
+            type Error = IdKindMismatch;
+
+            #[inline]
+            fn try_from(value: IlgdaId) -> Result<Self, Self::Error> {
+                match value {
+                    IlgdaId::$id_ty(inner) => Ok(<$ty>::from(inner)),
+                    id => Err(IdKindMismatch { expected: IdKind::$id_ty, id })
+                }
+            }
+        }
+        )+
+    };
+}
+
+impl_try_into! { Numeric |> u64 }
+impl_try_into! { String |> Box<str>, String }
+impl_try_into! { Bytes |> HeapArray<u8>, Vec<u8>, Box<[u8]> }
+impl_try_into! { Composite |> Box<[IlgdaId]>, Vec<IlgdaId> }
+
+#[cfg(feature = "uuid")]
+impl TryFrom<IlgdaId> for uuid::Uuid {
+    type Error = IdKindMismatch;
+
+    #[inline]
+    fn try_from(value: IlgdaId) -> Result<Self, Self::Error> {
+        match value {
+            IlgdaId::Uuid(bytes) => Ok(uuid::Uuid::from_bytes(bytes)),
+            id => Err(IdKindMismatch { expected: IdKind::Uuid, id })
+        }
+    }
+}
+
+impl Debug for IlgdaId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        #[cfg(feature = "uuid")]
+        let uuid;
+
+        let field: &dyn Debug = match self {
+            IlgdaId::Numeric(num) => num,
+            IlgdaId::String(str) => str,
+            IlgdaId::Bytes(bytes) => bytes,
+            #[cfg(feature = "uuid")]
+            IlgdaId::Uuid(bytes) => {
+                uuid = uuid::Uuid::from_bytes(*bytes);
+                &uuid
+            },
+            #[cfg(not(feature = "uuid"))]
+            IlgdaId::Uuid(bytes) => bytes,
+            IlgdaId::Composite(ids) => ids
+        };
+
+        f.debug_tuple("Id").field(field).finish()
+    }
+}
+
+fn write_hex(f: &mut Formatter<'_>, bytes: &[u8]) -> fmt::Result {
+    bytes.iter().try_for_each(|byte| write!(f, "{byte:02x}"))
+}
+
+fn write_uuid(f: &mut Formatter<'_>, bytes: &[u8; 16]) -> fmt::Result {
+    for (i, group) in [&bytes[..4], &bytes[4..6], &bytes[6..8], &bytes[8..10], &bytes[10..]].into_iter().enumerate() {
+        if i != 0 { f.write_char('-')? }
+        write_hex(f, group)?
+    }
+    Ok(())
+}
+
+// strings that would be read back as something else, or cut short inside of a composite
+fn needs_quotes(s: &str, nested: bool) -> bool {
+    match s.as_bytes().first() {
+        Some(b'"' | b'[') => true,
+        _ if nested && (s.is_empty() || s.contains([',', '[', ']'])) => true,
+        _ => {
+            let is_number = !s.is_empty() && s.bytes().all(|c| c.is_ascii_digit()) && s.parse::<u64>().is_ok();
+            let is_hex = s.strip_prefix("0x").is_some_and(|hex| hex.len() % 2 == 0 && hex.bytes().all(|c| c.is_ascii_hexdigit()));
+            is_number || is_hex || parse_uuid(s).is_some()
+        }
+    }
+}
+
+fn write_quoted(f: &mut Formatter<'_>, s: &str) -> fmt::Result {
+    f.write_char('"')?;
+    for c in s.chars() {
+        if matches!(c, '"' | '\\') { f.write_char('\\')? }
+        f.write_char(c)?
+    }
+    f.write_char('"')
+}
+
+fn write_id(f: &mut Formatter<'_>, id: &IlgdaId, nested: bool) -> fmt::Result {
+    match id {
+        IlgdaId::Numeric(num) => Display::fmt(num, f),
+        IlgdaId::String(str) if needs_quotes(str, nested) => write_quoted(f, str),
+        IlgdaId::String(str) => f.write_str(str),
+        IlgdaId::Bytes(bytes) => {
+            f.write_str("0x")?;
+            write_hex(f, bytes)
+        },
+        IlgdaId::Uuid(bytes) => write_uuid(f, bytes),
+        IlgdaId::Composite(ids) => {
+            f.write_char('[')?;
+            for (i, id) in ids.iter().enumerate() {
+                if i != 0 { f.write_char(',')? }
+                write_id(f, id, true)?
+            }
+            f.write_char(']')
+        }
+    }
+}
+
+/// Formats the id the way [`FromStr`] reads it back:
+/// numbers in decimal, strings as-is, bytes as `0x` prefixed hex,
+/// uuids in their hyphenated form and composites as `[a,b,c]`.
+///
+/// Strings that would read back as one of the other forms (e.g. `"42"`) are quoted, with `"` and `\` escaped
+/// by a backslash. So are strings inside of a composite that are empty or contain a `,`, `[` or `]`.
+impl Display for IlgdaId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write_id(f, self, false)
+    }
+}
+
+/// The error returned when parsing an [`IlgdaId`] fails
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseIdError {
+    /// composites were nested more than 64 levels deep
+    TooDeeplyNested
+}
+
+impl Display for ParseIdError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ParseIdError::TooDeeplyNested => "composite id nested too deeply"
+        })
+    }
+}
+
+impl Error for ParseIdError {}
+
+fn parse_hex(hex: &[u8], out: &mut [u8]) -> Option<()> {
+    fn nibble(digit: u8) -> Option<u8> {
+        (digit as char).to_digit(16).map(|d| d as u8)
+    }
+
+    if hex.len() != out.len() * 2 { return None }
+
+    for (byte, pair) in out.iter_mut().zip(hex.chunks_exact(2)) {
+        *byte = nibble(pair[0])? << 4 | nibble(pair[1])?;
+    }
+
+    Some(())
+}
+
+fn parse_uuid(s: &str) -> Option<[u8; 16]> {
+    let s = s.as_bytes();
+    if s.len() != 36 || [8, 13, 18, 23].into_iter().any(|i| s[i] != b'-') {
+        return None
+    }
+
+    let mut bytes = [0; 16];
+    let mut groups = s.split(|&c| c == b'-');
+    for range in [0..4, 4..6, 6..8, 8..10, 10..16] {
+        parse_hex(groups.next()?, &mut bytes[range])?
+    }
+
+    Some(bytes)
+}
+
+// every form but composites
+fn parse_single(s: &str) -> IlgdaId {
+    if !s.is_empty() && s.bytes().all(|c| c.is_ascii_digit()) {
+        // too big to be a number, so it's a string
+        return s.parse().map_or_else(|_| IlgdaId::from(s), IlgdaId::Numeric)
+    }
+
+    if let Some(hex) = s.strip_prefix("0x") {
+        let mut bytes = HeapArray::from_element(hex.len() / 2, 0);
+        if parse_hex(hex.as_bytes(), &mut bytes).is_some() {
+            return IlgdaId::Bytes(bytes)
+        }
+    }
+
+    match parse_uuid(s) {
+        Some(bytes) => IlgdaId::Uuid(bytes),
+        None => IlgdaId::from(s)
+    }
+}
+
+fn unquote(quoted: &str) -> String {
+    let mut chars = quoted.chars();
+    let mut unquoted = String::with_capacity(quoted.len());
+    while let Some(c) = chars.next() {
+        unquoted.push(if c == '\\' { chars.next().unwrap_or(c) } else { c });
+    }
+    unquoted
+}
+
+// the index of the bracket closing each `[` and of the quote closing each `"` an element starts with,
+// usize::MAX for ones never closed. brackets inside of quotes don't count
+fn closing_delimiters(s: &[u8]) -> Vec<usize> {
+    let mut closing = vec![usize::MAX; s.len()];
+    let mut open = vec![];
+    let mut i = 0;
+    while i < s.len() {
+        match s[i] {
+            b'[' => open.push(i),
+            b']' => if let Some(start) = open.pop() { closing[start] = i },
+            b'"' if i == 0 || matches!(s[i - 1], b'[' | b',') => {
+                let mut end = i + 1;
+                while end < s.len() && s[end] != b'"' {
+                    end += if s[end] == b'\\' { 2 } else { 1 };
+                }
+
+                // once a quote is left open no later element can start with one either,
+                // so this never scans the rest of the input more than once
+                if end < s.len() {
+                    closing[i] = end;
+                    i = end;
+                }
+            },
+            _ => {}
+        }
+        i += 1;
+    }
+    closing
+}
+
+/// Parses the forms written by [`Display`]; anything that isn't one of them is a string id,
+/// including `0x` prefixed ids that aren't valid hex, bracketed ids whose brackets don't balance
+/// and quoted ids whose quotes don't close.
+///
+/// Fails only for composites nested more than 64 levels deep.
+impl FromStr for IlgdaId {
+    type Err = ParseIdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bytes = s.as_bytes();
+        if !matches!(bytes.first(), Some(b'[' | b'"')) {
+            return Ok(parse_single(s))
+        }
+
+        // a composite or quoted string is a `[` or `"` closed by the last character of its element,
+        // inside of one every bracket is balanced, so skipping a nested one is a single jump
+        let closing = closing_delimiters(bytes);
+        if closing[0] != bytes.len() - 1 {
+            return Ok(IlgdaId::from(s))
+        }
+
+        if bytes[0] == b'"' {
+            return Ok(IlgdaId::from(unquote(&s[1..s.len() - 1])))
+        }
+
+        // the composites still being parsed, with the index of their closing bracket
+        let mut open = vec![(vec![], bytes.len() - 1)];
+        let mut start = 1;
+        loop {
+            let close = open.last().expect("an element is always inside a composite").1;
+
+            let (mut id, mut end) = if start == close && bytes[start - 1] == b'[' {
+                // nothing between the brackets
+                let (elements, close) = open.pop().expect("an element is always inside a composite");
+                (IlgdaId::from(elements), close + 1)
+            } else {
+                // elements end on a comma that isn't inside a nested composite or quotes
+                let mut end = start;
+                while end < close && bytes[end] != b',' {
+                    end = match bytes[end] {
+                        b'[' => closing[end] + 1,
+                        b'"' if closing[end] != usize::MAX => closing[end] + 1,
+                        _ => end + 1
+                    };
+                }
+
+                if bytes[start] == b'[' && closing[start] == end - 1 {
+                    if open.len() == MAX_NESTING {
+                        return Err(ParseIdError::TooDeeplyNested)
+                    }
+                    open.push((vec![], end - 1));
+                    start += 1;
+                    continue
+                }
+
+                if bytes[start] == b'"' && closing[start] == end - 1 {
+                    (IlgdaId::from(unquote(&s[start + 1..end - 1])), end)
+                } else {
+                    (parse_single(&s[start..end]), end)
+                }
+            };
+
+            // finish every composite the element was the last one of
+            loop {
+                let Some((elements, close)) = open.last_mut() else { return Ok(id) };
+                elements.push(id);
+                if end < *close {
+                    start = end + 1;
+                    break
+                }
+
+                let (elements, close) = open.pop().expect("just looked at it");
+                id = IlgdaId::from(elements);
+                end = close + 1;
+            }
+        }
+    }
+}
+
+// HeapArray doesn't implement Hash, so hash the slice behind it instead
+impl Hash for IlgdaId {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        mem::discriminant(self).hash(state);
+        match self {
+            IlgdaId::Numeric(num) => num.hash(state),
+            IlgdaId::String(str) => str.hash(state),
+            IlgdaId::Bytes(bytes) => <[u8]>::hash(bytes, state),
+            IlgdaId::Uuid(bytes) => bytes.hash(state),
+            IlgdaId::Composite(ids) => ids.hash(state)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{IlgdaId, ParseIdError, MAX_NESTING};
+
+    fn nested(depth: usize) -> IlgdaId {
+        (0..depth).fold(IlgdaId::from(1_u64), |id, _| IlgdaId::from(vec![id]))
+    }
+
+    #[test]
+    fn display_round_trips() {
+        let ids = [
+            IlgdaId::from(42_u64),
+            IlgdaId::from(u64::MAX),
+            IlgdaId::from("hello"),
+            IlgdaId::from(""),
+            IlgdaId::from([0xde, 0xad, 0xbe, 0xef]),
+            IlgdaId::from(Vec::<u8>::new()),
+            IlgdaId::Uuid(*b"0123456789abcdef"),
+            IlgdaId::Composite(Box::new([])),
+            IlgdaId::from(vec![IlgdaId::from(1_u64), IlgdaId::from("a"), IlgdaId::from(vec![IlgdaId::from([1])])]),
+            IlgdaId::from(vec![IlgdaId::Composite(Box::new([])), IlgdaId::Composite(Box::new([]))]),
+            nested(MAX_NESTING),
+            // strings that would read back as something else on their own
+            IlgdaId::from("42"),
+            IlgdaId::from("0xab"),
+            IlgdaId::from("0x"),
+            IlgdaId::from("11111111-1111-1111-1111-111111111111"),
+            IlgdaId::from("[1]"),
+            IlgdaId::from("[a"),
+            IlgdaId::from(r#""quoted""#),
+            IlgdaId::from(r#""\"#),
+            IlgdaId::from("a,b"),
+            // strings that would split or close a composite
+            IlgdaId::from(vec![IlgdaId::from("")]),
+            IlgdaId::from(vec![IlgdaId::from("a,b")]),
+            IlgdaId::from(vec![IlgdaId::from("[1]"), IlgdaId::from("]"), IlgdaId::from("42")]),
+            IlgdaId::from(vec![IlgdaId::from(r#"""#), IlgdaId::from(r"\"), IlgdaId::from(r#"a"b"#), IlgdaId::from(r#""[,]\"#)]),
+            IlgdaId::from(vec![IlgdaId::from(vec![IlgdaId::from(""), IlgdaId::from("")]), IlgdaId::from("")])
+        ];
+
+        for id in ids {
+            assert_eq!(id.to_string().parse(), Ok(id.clone()), "{id}");
+        }
+    }
+
+    #[test]
+    fn display_forms() {
+        assert_eq!(IlgdaId::from(7_u64).to_string(), "7");
+        assert_eq!(IlgdaId::from([0xab, 0x01]).to_string(), "0xab01");
+        assert_eq!(IlgdaId::Uuid([0x11; 16]).to_string(), "11111111-1111-1111-1111-111111111111");
+        assert_eq!(IlgdaId::from(vec![IlgdaId::from(1_u64), IlgdaId::from("b")]).to_string(), "[1,b]");
+
+        assert_eq!(IlgdaId::from("a,b").to_string(), "a,b");
+        assert_eq!(IlgdaId::from(r#"say "hi""#).to_string(), r#"say "hi""#);
+        assert_eq!(IlgdaId::from("42").to_string(), r#""42""#);
+        assert_eq!(IlgdaId::from("[1]").to_string(), r#""[1]""#);
+        assert_eq!(IlgdaId::from(r#""hi\""#).to_string(), r#""\"hi\\\"""#);
+        assert_eq!(IlgdaId::from(vec![IlgdaId::from("")]).to_string(), r#"[""]"#);
+        assert_eq!(IlgdaId::from(vec![IlgdaId::from("a,b"), IlgdaId::from("c")]).to_string(), r#"["a,b",c]"#);
+        assert_eq!(IlgdaId::from(vec![IlgdaId::from(r"a\b")]).to_string(), r"[a\b]");
+    }
+
+    #[test]
+    fn malformed_forms_are_strings() {
+        for s in ["0xhello", "0xabc", "[a]]", "[[a]", "[a][b]", "]", "[", "18446744073709551616", r#"""#, r#""a"#, r#""a"b"#] {
+            assert_eq!(s.parse(), Ok(IlgdaId::from(s)), "{s}");
+        }
+
+        assert_eq!(IlgdaId::from("0xhello").to_string().parse(), Ok(IlgdaId::from("0xhello")));
+        assert_eq!(
+            "[x,[a][b],0xzz]".parse(),
+            Ok(IlgdaId::from(vec![IlgdaId::from("x"), IlgdaId::from("[a][b]"), IlgdaId::from("0xzz")]))
+        );
+        assert_eq!(
+            r#"["a"b,c"d]"#.parse(),
+            Ok(IlgdaId::from(vec![IlgdaId::from(r#""a"b"#), IlgdaId::from(r#"c"d"#)]))
+        );
+        assert_eq!(r#"["a]"#.parse(), Ok(IlgdaId::from(vec![IlgdaId::from(r#""a"#)])));
+    }
+
+    #[test]
+    fn nesting_is_limited() {
+        assert_eq!(nested(MAX_NESTING + 1).to_string().parse::<IlgdaId>(), Err(ParseIdError::TooDeeplyNested));
+
+        let deep = "[".repeat(100_000) + &"]".repeat(100_000);
+        assert_eq!(deep.parse::<IlgdaId>(), Err(ParseIdError::TooDeeplyNested));
+
+        // unbalanced, so just a string no matter how deep
+        let unbalanced = "[".repeat(100_000) + &"]".repeat(99_999);
+        assert_eq!(unbalanced.parse(), Ok(IlgdaId::from(unbalanced.as_str())));
+    }
+
+    #[test]
+    fn wide_composites_parse() {
+        let wide = IlgdaId::from((0..10_000_u64).map(IlgdaId::from).collect::<Vec<_>>());
+        assert_eq!(wide.to_string().parse(), Ok(wide));
+    }
+}
@@ -0,0 +1,628 @@
+use std::{
+    borrow::Cow,
+    fmt::{self, Display, Formatter},
+    num::FpCategory
+};
+use serde::{
+    Deserialize, Deserializer, Serialize, Serializer,
+    de::{
+        DeserializeSeed, Error as DeserializeError, EnumAccess, Expected, IgnoredAny, MapAccess,
+        SeqAccess, Unexpected, VariantAccess, Visitor
+    },
+    ser::SerializeMap
+};
+
+use super::{IlgdaId, IlgdaIdRef, MAX_NESTING, parse_hex, parse_uuid, write_hex, write_uuid};
+
+// human readable formats write bytes, uuids and composites as single entry maps,
+// since a plain sequence already stands for bytes
+const BYTES_KEY: &str = "bytes";
+const UUID_KEY: &str = "uuid";
+const COMPOSITE_KEY: &str = "composite";
+const TAGGED_KEYS: &[&str] = &[BYTES_KEY, UUID_KEY, COMPOSITE_KEY];
+
+// binary formats write ids as a plain enum
+const ID_NAME: &str = "IlgdaId";
+const VARIANTS: &[&str] = &["Numeric", "String", "Bytes", "Uuid", "Composite"];
+
+// caps how much is allocated up front for a size hint
+const MAX_PREALLOCATION: usize = 4096;
+
+// what IlgdaId and IlgdaIdRef have in common, so both serialize the same way
+enum Parts<'a, C: ?Sized> {
+    Numeric(u64),
+    String(&'a str),
+    Bytes(&'a [u8]),
+    Uuid(&'a [u8; 16]),
+    Composite(&'a C)
+}
+
+struct RawBytes<'a>(&'a [u8]);
+
+impl Serialize for RawBytes<'_> {
+    #[inline]
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+        serializer.serialize_bytes(self.0)
+    }
+}
+
+struct Hex<'a>(&'a [u8]);
+
+impl Display for Hex<'_> {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write_hex(f, self.0)
+    }
+}
+
+struct Hyphenated<'a>(&'a [u8; 16]);
+
+impl Display for Hyphenated<'_> {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write_uuid(f, self.0)
+    }
+}
+
+struct Collected<T>(T);
+
+impl<T: Display> Serialize for Collected<T> {
+    #[inline]
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+        serializer.collect_str(&self.0)
+    }
+}
+
+fn serialize_tagged<S: Serializer, T: Serialize + ?Sized>(serializer: S, key: &str, value: &T) -> Result<S::Ok, S::Error> {
+    let mut map = serializer.serialize_map(Some(1))?;
+    map.serialize_entry(key, value)?;
+    map.end()
+}
+
+impl<C: Serialize + ?Sized> Serialize for Parts<'_, C> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+        if serializer.is_human_readable() {
+            return match *self {
+                Parts::Numeric(num) => serializer.serialize_u64(num),
+                Parts::String(str) => serializer.serialize_str(str),
+                Parts::Bytes(bytes) => serialize_tagged(serializer, BYTES_KEY, &Collected(Hex(bytes))),
+                Parts::Uuid(bytes) => serialize_tagged(serializer, UUID_KEY, &Collected(Hyphenated(bytes))),
+                Parts::Composite(ids) => serialize_tagged(serializer, COMPOSITE_KEY, ids)
+            }
+        }
+
+        match *self {
+            Parts::Numeric(num) => serializer.serialize_newtype_variant(ID_NAME, 0, VARIANTS[0], &num),
+            Parts::String(str) => serializer.serialize_newtype_variant(ID_NAME, 1, VARIANTS[1], str),
+            Parts::Bytes(bytes) => serializer.serialize_newtype_variant(ID_NAME, 2, VARIANTS[2], &RawBytes(bytes)),
+            Parts::Uuid(bytes) => serializer.serialize_newtype_variant(ID_NAME, 3, VARIANTS[3], bytes),
+            Parts::Composite(ids) => serializer.serialize_newtype_variant(ID_NAME, 4, VARIANTS[4], ids)
+        }
+    }
+}
+
+/// Human readable formats (e.g. JSON) get numbers and strings as-is,
+/// and bytes, uuids and composites as single entry maps:
+/// `{"bytes": "dead01"}`, `{"uuid": "67e55044-10b1-426f-9247-bb680e5fe0c8"}`, `{"composite": [1, "a"]}`.
+///
+/// Binary formats (e.g. bincode) get a plain enum, which keeps them compact
+/// and doesn't need a self describing format to read back.
+impl Serialize for IlgdaId {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+        let parts = match self {
+            IlgdaId::Numeric(num) => Parts::Numeric(*num),
+            IlgdaId::String(str) => Parts::String(str),
+            IlgdaId::Bytes(bytes) => Parts::Bytes(bytes),
+            IlgdaId::Uuid(bytes) => Parts::Uuid(bytes),
+            IlgdaId::Composite(ids) => Parts::Composite(&**ids)
+        };
+
+        parts.serialize(serializer)
+    }
+}
+
+impl Serialize for IlgdaIdRef<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+        let parts = match self {
+            IlgdaIdRef::Numeric(num) => Parts::Numeric(*num),
+            IlgdaIdRef::String(str) => Parts::String(str),
+            IlgdaIdRef::Bytes(bytes) => Parts::Bytes(bytes),
+            IlgdaIdRef::Uuid(bytes) => Parts::Uuid(bytes),
+            IlgdaIdRef::Composite(ids) => Parts::Composite(&**ids)
+        };
+
+        parts.serialize(serializer)
+    }
+}
+
+struct ExpectedUnsigned;
+struct ExpectedSafeUnsignedInteger;
+struct ExpectedSingleEntry;
+struct ExpectedVariantIndex;
+
+impl Expected for ExpectedUnsigned {
+    #[inline]
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        formatter.write_str("expected an unsigned integer")
+    }
+}
+
+impl Expected for ExpectedSafeUnsignedInteger {
+    #[inline]
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        formatter.write_str("a float that can safely be represented as an unsigned integer")
+    }
+}
+
+impl Expected for ExpectedSingleEntry {
+    #[inline]
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        formatter.write_str("a map with a single entry")
+    }
+}
+
+impl Expected for ExpectedVariantIndex {
+    #[inline]
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(formatter, "a variant index below {}", VARIANTS.len())
+    }
+}
+
+fn collect_bytes<'a, A: SeqAccess<'a>>(mut seq: A) -> Result<Vec<u8>, A::Error> {
+    let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(MAX_PREALLOCATION));
+    while let Some(byte) = seq.next_element()? {
+        bytes.push(byte)
+    }
+
+    Ok(bytes)
+}
+
+enum TaggedKey {
+    Bytes,
+    Uuid,
+    Composite
+}
+
+impl<'de> Deserialize<'de> for TaggedKey {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct KeyVisitor;
+
+        impl<'a> Visitor<'a> for KeyVisitor {
+            type Value = TaggedKey;
+
+            fn expecting(&self, formatter: &mut Formatter) -> fmt::Result {
+                write!(formatter, "one of `{BYTES_KEY}`, `{UUID_KEY}` or `{COMPOSITE_KEY}`")
+            }
+
+            fn visit_str<E>(self, v: &str) -> Result<Self::Value, E> where E: DeserializeError {
+                match v {
+                    BYTES_KEY => Ok(TaggedKey::Bytes),
+                    UUID_KEY => Ok(TaggedKey::Uuid),
+                    COMPOSITE_KEY => Ok(TaggedKey::Composite),
+                    _ => Err(E::unknown_variant(v, TAGGED_KEYS))
+                }
+            }
+        }
+
+        deserializer.deserialize_identifier(KeyVisitor)
+    }
+}
+
+enum Variant {
+    Numeric,
+    String,
+    Bytes,
+    Uuid,
+    Composite
+}
+
+impl<'de> Deserialize<'de> for Variant {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct VariantVisitor;
+
+        impl<'a> Visitor<'a> for VariantVisitor {
+            type Value = Variant;
+
+            fn expecting(&self, formatter: &mut Formatter) -> fmt::Result {
+                formatter.write_str("an id variant")
+            }
+
+            fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E> where E: DeserializeError {
+                match v {
+                    0 => Ok(Variant::Numeric),
+                    1 => Ok(Variant::String),
+                    2 => Ok(Variant::Bytes),
+                    3 => Ok(Variant::Uuid),
+                    4 => Ok(Variant::Composite),
+                    _ => Err(E::invalid_value(Unexpected::Unsigned(v), &ExpectedVariantIndex))
+                }
+            }
+
+            fn visit_str<E>(self, v: &str) -> Result<Self::Value, E> where E: DeserializeError {
+                match VARIANTS.iter().position(|&variant| variant == v) {
+                    Some(i) => self.visit_u64(i as u64),
+                    None => Err(E::unknown_variant(v, VARIANTS))
+                }
+            }
+        }
+
+        deserializer.deserialize_identifier(VariantVisitor)
+    }
+}
+
+struct StrContents<'a>(Cow<'a, str>);
+
+impl<'de> Deserialize<'de> for StrContents<'de> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct StrVisitor;
+
+        impl<'a> Visitor<'a> for StrVisitor {
+            type Value = StrContents<'a>;
+
+            fn expecting(&self, formatter: &mut Formatter) -> fmt::Result {
+                formatter.write_str("a string")
+            }
+
+            fn visit_borrowed_str<E>(self, v: &'a str) -> Result<Self::Value, E> where E: DeserializeError {
+                Ok(StrContents(Cow::Borrowed(v)))
+            }
+
+            fn visit_str<E>(self, v: &str) -> Result<Self::Value, E> where E: DeserializeError {
+                Ok(StrContents(Cow::Owned(v.to_owned())))
+            }
+
+            fn visit_string<E>(self, v: String) -> Result<Self::Value, E> where E: DeserializeError {
+                Ok(StrContents(Cow::Owned(v)))
+            }
+        }
+
+        deserializer.deserialize_str(StrVisitor)
+    }
+}
+
+// raw bytes, or a hex string in human readable formats
+struct ByteContents<'a>(Cow<'a, [u8]>);
+
+impl<'de> Deserialize<'de> for ByteContents<'de> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct BytesVisitor;
+
+        impl<'a> Visitor<'a> for BytesVisitor {
+            type Value = ByteContents<'a>;
+
+            fn expecting(&self, formatter: &mut Formatter) -> fmt::Result {
+                formatter.write_str("bytes or a hex string")
+            }
+
+            fn visit_str<E>(self, v: &str) -> Result<Self::Value, E> where E: DeserializeError {
+                let mut bytes = vec![0; v.len() / 2];
+                match parse_hex(v.as_bytes(), &mut bytes) {
+                    Some(()) => Ok(ByteContents(Cow::Owned(bytes))),
+                    None => Err(E::invalid_value(Unexpected::Str(v), &self))
+                }
+            }
+
+            fn visit_borrowed_bytes<E>(self, v: &'a [u8]) -> Result<Self::Value, E> where E: DeserializeError {
+                Ok(ByteContents(Cow::Borrowed(v)))
+            }
+
+            fn visit_bytes<E>(self, v: &[u8]) -> Result<Self::Value, E> where E: DeserializeError {
+                Ok(ByteContents(Cow::Owned(v.to_owned())))
+            }
+
+            fn visit_byte_buf<E>(self, v: Vec<u8>) -> Result<Self::Value, E> where E: DeserializeError {
+                Ok(ByteContents(Cow::Owned(v)))
+            }
+
+            fn visit_seq<A: SeqAccess<'a>>(self, seq: A) -> Result<Self::Value, A::Error> {
+                collect_bytes(seq).map(|bytes| ByteContents(Cow::Owned(bytes)))
+            }
+        }
+
+        match deserializer.is_human_readable() {
+            true => deserializer.deserialize_any(BytesVisitor),
+            false => deserializer.deserialize_bytes(BytesVisitor)
+        }
+    }
+}
+
+// a hyphenated uuid string, or its 16 bytes
+struct UuidBytes([u8; 16]);
+
+impl<'de> Deserialize<'de> for UuidBytes {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct UuidVisitor;
+
+        impl<'a> Visitor<'a> for UuidVisitor {
+            type Value = UuidBytes;
+
+            fn expecting(&self, formatter: &mut Formatter) -> fmt::Result {
+                formatter.write_str("a hyphenated uuid or 16 bytes")
+            }
+
+            fn visit_str<E>(self, v: &str) -> Result<Self::Value, E> where E: DeserializeError {
+                match parse_uuid(v) {
+                    Some(bytes) => Ok(UuidBytes(bytes)),
+                    None => Err(E::invalid_value(Unexpected::Str(v), &self))
+                }
+            }
+
+            fn visit_bytes<E>(self, v: &[u8]) -> Result<Self::Value, E> where E: DeserializeError {
+                match <[u8; 16]>::try_from(v) {
+                    Ok(bytes) => Ok(UuidBytes(bytes)),
+                    Err(_) => Err(E::invalid_length(v.len(), &self))
+                }
+            }
+
+            fn visit_seq<A: SeqAccess<'a>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+                let mut bytes = [0; 16];
+                for (i, byte) in bytes.iter_mut().enumerate() {
+                    *byte = seq.next_element()?.ok_or_else(|| A::Error::invalid_length(i, &self))?;
+                }
+
+                match seq.next_element::<IgnoredAny>()? {
+                    None => Ok(UuidBytes(bytes)),
+                    Some(_) => Err(A::Error::invalid_length(17, &self))
+                }
+            }
+        }
+
+        deserializer.deserialize_any(UuidVisitor)
+    }
+}
+
+/// Reads either form written by [`IlgdaId`]'s `Serialize` impl, picked by [`Deserializer::is_human_readable`].
+///
+/// Human readable formats also accept bytes as a plain sequence, and uuids as a sequence of 16 bytes.
+/// Composites nested more than 64 levels deep are rejected.
+impl<'de> Deserialize<'de> for IlgdaIdRef<'de> {
+    #[inline]
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        IdSeed { depth: 0 }.deserialize(deserializer)
+    }
+}
+
+// an id inside of `depth` composites, deserialized without recursing any deeper than MAX_NESTING
+struct IdSeed {
+    depth: usize
+}
+
+impl IdSeed {
+    fn composite<E: DeserializeError>(&self) -> Result<CompositeSeed, E> {
+        match self.depth < MAX_NESTING {
+            true => Ok(CompositeSeed { depth: self.depth + 1 }),
+            false => Err(E::custom("composite id is nested too deeply"))
+        }
+    }
+}
+
+impl<'de> DeserializeSeed<'de> for IdSeed {
+    type Value = IlgdaIdRef<'de>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        struct IdVisitor(IdSeed);
+
+        impl<'a> Visitor<'a> for IdVisitor {
+            type Value = IlgdaIdRef<'a>;
+
+            fn expecting(&self, formatter: &mut Formatter) -> fmt::Result {
+                formatter.write_str("expecting either an unsigned integer, string, an array of bytes, a uuid, or a composite id")
+            }
+
+            fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E> where E: DeserializeError {
+                match u64::try_from(v) {
+                    Ok(v) => Ok(IlgdaIdRef::Numeric(v)),
+                    Err(_) => Err(E::invalid_value(Unexpected::Signed(v), &ExpectedUnsigned))
+                }
+            }
+
+            fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E> where E: DeserializeError {
+                Ok(IlgdaIdRef::Numeric(v))
+            }
+
+            fn visit_f64<E>(self, v: f64) -> Result<Self::Value, E> where E: DeserializeError {
+                const MAX_SAFE_INTEGER: f64 = ((1_u64 << f64::MANTISSA_DIGITS) - 1) as f64;
+
+                match v.classify() {
+                    FpCategory::Zero => Ok(IlgdaIdRef::Numeric(0)),
+                    FpCategory::Normal if v.trunc() == v && (0.0..=MAX_SAFE_INTEGER).contains(&v) => Ok(IlgdaIdRef::Numeric(v as u64)),
+                    _ => Err(E::invalid_value(Unexpected::Float(v), &ExpectedSafeUnsignedInteger))
+                }
+            }
+
+            fn visit_borrowed_str<E>(self, v: &'a str) -> Result<Self::Value, E> where E: DeserializeError {
+                Ok(IlgdaIdRef::String(Cow::Borrowed(v)))
+            }
+
+            fn visit_str<E>(self, v: &str) -> Result<Self::Value, E> where E: DeserializeError {
+                Ok(IlgdaIdRef::String(Cow::Owned(v.to_owned())))
+            }
+
+            fn visit_string<E>(self, v: String) -> Result<Self::Value, E> where E: DeserializeError {
+                Ok(IlgdaIdRef::String(Cow::Owned(v)))
+            }
+
+            fn visit_borrowed_bytes<E>(self, v: &'a [u8]) -> Result<Self::Value, E> where E: DeserializeError {
+                Ok(IlgdaIdRef::Bytes(Cow::Borrowed(v)))
+            }
+
+            fn visit_bytes<E>(self, v: &[u8]) -> Result<Self::Value, E> where E: DeserializeError {
+                Ok(IlgdaIdRef::Bytes(Cow::Owned(v.to_owned())))
+            }
+
+            fn visit_byte_buf<E>(self, v: Vec<u8>) -> Result<Self::Value, E> where E: DeserializeError {
+                Ok(IlgdaIdRef::Bytes(Cow::Owned(v)))
+            }
+
+            fn visit_seq<A: SeqAccess<'a>>(self, seq: A) -> Result<Self::Value, A::Error> {
+                collect_bytes(seq).map(|bytes| IlgdaIdRef::Bytes(Cow::Owned(bytes)))
+            }
+
+            fn visit_map<A: MapAccess<'a>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+                let id = match map.next_key()? {
+                    Some(TaggedKey::Bytes) => IlgdaIdRef::Bytes(map.next_value::<ByteContents>()?.0),
+                    Some(TaggedKey::Uuid) => IlgdaIdRef::Uuid(map.next_value::<UuidBytes>()?.0),
+                    Some(TaggedKey::Composite) => IlgdaIdRef::Composite(map.next_value_seed(self.0.composite()?)?),
+                    None => return Err(A::Error::invalid_length(0, &ExpectedSingleEntry))
+                };
+
+                match map.next_key::<IgnoredAny>()? {
+                    None => Ok(id),
+                    Some(_) => Err(A::Error::invalid_length(2, &ExpectedSingleEntry))
+                }
+            }
+
+            fn visit_enum<A: EnumAccess<'a>>(self, data: A) -> Result<Self::Value, A::Error> {
+                let (variant, value) = data.variant()?;
+                Ok(match variant {
+                    Variant::Numeric => IlgdaIdRef::Numeric(value.newtype_variant()?),
+                    Variant::String => IlgdaIdRef::String(value.newtype_variant::<StrContents>()?.0),
+                    Variant::Bytes => IlgdaIdRef::Bytes(value.newtype_variant::<ByteContents>()?.0),
+                    Variant::Uuid => IlgdaIdRef::Uuid(value.newtype_variant()?),
+                    Variant::Composite => IlgdaIdRef::Composite(value.newtype_variant_seed(self.0.composite()?)?)
+                })
+            }
+        }
+
+        match deserializer.is_human_readable() {
+            true => deserializer.deserialize_any(IdVisitor(self)),
+            false => deserializer.deserialize_enum(ID_NAME, VARIANTS, IdVisitor(self))
+        }
+    }
+}
+
+// the elements of a composite that is itself inside of `depth - 1` composites
+struct CompositeSeed {
+    depth: usize
+}
+
+impl<'de> DeserializeSeed<'de> for CompositeSeed {
+    type Value = Vec<IlgdaIdRef<'de>>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        struct CompositeVisitor(CompositeSeed);
+
+        impl<'a> Visitor<'a> for CompositeVisitor {
+            type Value = Vec<IlgdaIdRef<'a>>;
+
+            fn expecting(&self, formatter: &mut Formatter) -> fmt::Result {
+                formatter.write_str("a sequence of ids")
+            }
+
+            fn visit_seq<A: SeqAccess<'a>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+                let mut ids = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(MAX_PREALLOCATION));
+                while let Some(id) = seq.next_element_seed(IdSeed { depth: self.0.depth })? {
+                    ids.push(id);
+                }
+                Ok(ids)
+            }
+        }
+
+        deserializer.deserialize_seq(CompositeVisitor(self))
+    }
+}
+
+impl<'de> Deserialize<'de> for IlgdaId {
+    #[inline]
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        IlgdaIdRef::deserialize(deserializer).map(IlgdaId::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+    use super::{IlgdaId, IlgdaIdRef, MAX_NESTING};
+
+    fn nested(depth: usize) -> IlgdaId {
+        (0..depth).fold(IlgdaId::from(1_u64), |id, _| IlgdaId::from(vec![id]))
+    }
+
+    fn ids() -> Vec<IlgdaId> {
+        vec![
+            IlgdaId::from(0_u64),
+            IlgdaId::from(u64::MAX),
+            IlgdaId::from("hello"),
+            IlgdaId::from(""),
+            IlgdaId::from([0xde, 0xad, 0xbe, 0xef]),
+            IlgdaId::from(Vec::<u8>::new()),
+            IlgdaId::Uuid(*b"0123456789abcdef"),
+            IlgdaId::Composite(Box::new([])),
+            IlgdaId::from(vec![IlgdaId::from(1_u64), IlgdaId::from("a"), IlgdaId::from([1])]),
+            nested(MAX_NESTING)
+        ]
+    }
+
+    #[test]
+    fn json_round_trips() {
+        for id in ids() {
+            // serde_json's parser stops at 128 levels on its own, and every composite takes two
+            if id != nested(MAX_NESTING) {
+                let json = serde_json::to_string(&id).unwrap();
+                assert_eq!(serde_json::from_str::<IlgdaId>(&json).unwrap(), id, "{json}");
+                assert_eq!(serde_json::from_str::<IlgdaIdRef>(&json).unwrap(), IlgdaIdRef::from(&id), "{json}");
+            }
+
+            let value = serde_json::to_value(&id).unwrap();
+            assert_eq!(serde_json::from_value::<IlgdaId>(value).unwrap(), id);
+        }
+    }
+
+    #[test]
+    fn bincode_round_trips() {
+        for id in ids() {
+            let bytes = bincode::serialize(&id).unwrap();
+            assert_eq!(bincode::deserialize::<IlgdaId>(&bytes).unwrap(), id);
+            assert_eq!(bincode::deserialize::<IlgdaIdRef>(&bytes).unwrap(), IlgdaIdRef::from(&id));
+        }
+    }
+
+    #[test]
+    fn json_forms() {
+        let json = serde_json::to_value(IlgdaId::from(vec![
+            IlgdaId::from(7_u64),
+            IlgdaId::from("a"),
+            IlgdaId::from([0xab]),
+            IlgdaId::Uuid([0x11; 16])
+        ])).unwrap();
+
+        assert_eq!(json, serde_json::json!({ "composite": [
+            7,
+            "a",
+            { "bytes": "ab" },
+            { "uuid": "11111111-1111-1111-1111-111111111111" }
+        ]}));
+
+        // the lenient forms
+        assert_eq!(serde_json::from_str::<IlgdaId>("[1,2]").unwrap(), IlgdaId::from([1, 2]));
+        assert_eq!(serde_json::from_str::<IlgdaId>("7.0").unwrap(), IlgdaId::from(7_u64));
+        assert_eq!(serde_json::from_str::<IlgdaId>(r#"{"uuid":[0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,1]}"#).unwrap(), IlgdaId::Uuid(1_u128.to_be_bytes()));
+
+        for invalid in ["-1", "0.5", "{}", r#"{"bytes":"abc"}"#, r#"{"bytes":"","uuid":""}"#, r#"{"other":1}"#] {
+            assert!(serde_json::from_str::<IlgdaId>(invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn borrows_where_it_can() {
+        let json = r#""hello""#;
+        assert!(matches!(serde_json::from_str(json).unwrap(), IlgdaIdRef::String(Cow::Borrowed("hello"))));
+
+        let bytes = bincode::serialize(&IlgdaId::from([1, 2, 3])).unwrap();
+        assert!(matches!(bincode::deserialize(&bytes).unwrap(), IlgdaIdRef::Bytes(Cow::Borrowed([1, 2, 3]))));
+    }
+
+    #[test]
+    fn nesting_is_limited() {
+        let too_deep = nested(MAX_NESTING + 1);
+        let err = serde_json::from_value::<IlgdaId>(serde_json::to_value(&too_deep).unwrap()).unwrap_err();
+        assert!(err.to_string().contains("nested too deeply"), "{err}");
+        let err = bincode::deserialize::<IlgdaId>(&bincode::serialize(&too_deep).unwrap()).unwrap_err();
+        assert!(err.to_string().contains("nested too deeply"), "{err}");
+
+        // would overflow the stack if nothing stopped it
+        let hostile = [4_u32.to_le_bytes().as_slice(), &1_u64.to_le_bytes()].concat().repeat(100_000);
+        let err = bincode::deserialize::<IlgdaId>(&hostile).unwrap_err();
+        assert!(err.to_string().contains("nested too deeply"), "{err}");
+    }
+}
@@ -1,3 +1,4 @@
+#[cfg(feature = "async-channels")]
 pub mod async_channels;
 
 pub mod entity;
\ No newline at end of file